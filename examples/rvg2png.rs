@@ -1,6 +1,6 @@
 use pix::rgb::{Rgba8p, SRgba8};
 use pix::Raster;
-use rvg::Graphic;
+use rvg::{FsResolver, Graphic};
 use std::fs::File;
 use std::io::Read;
 
@@ -9,26 +9,110 @@ pub fn write_png(
     filename: &str,
 ) -> std::io::Result<()> {
     let fl = std::fs::File::create(filename)?;
-    let ref mut bw = std::io::BufWriter::new(fl);
+    let bw = &mut std::io::BufWriter::new(fl);
     let mut enc = png_pong::FrameEncoder::new(bw);
     enc.still(&raster).unwrap();
     Ok(())
 }
 
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    assert_eq!(args.len(), 2);
+/// Load an RVG file, with external bitmaps found relative to it.
+fn load(filename: &str) -> Graphic {
     let mut rvg = Vec::new();
-    let mut f = File::open(&args[1]).unwrap();
+    let mut f = File::open(filename).unwrap();
     f.read_to_end(&mut rvg).unwrap();
 
-    let graphic = Graphic::load(std::io::Cursor::new(&rvg)).unwrap();
+    let mut graphic = Graphic::load(std::io::Cursor::new(&rvg)).unwrap();
+    let dir = std::path::Path::new(filename).parent().unwrap();
+    if graphic.resolve(&FsResolver::new(dir)).is_none() {
+        eprintln!("Some external bitmaps could not be loaded");
+    }
+    graphic
+}
+
+/// `rvg2png apng <file.rvg> [fps]`: export the first model's animation.
+fn apng(args: &[String]) {
+    assert!(args.len() == 1 || args.len() == 2);
+    let fps = args.get(1).map_or(30.0, |fps| fps.parse().unwrap());
+    let graphic = load(&args[0]);
+
+    let clip = rvg::render_clip(&graphic, 0, fps).unwrap();
+    let fl = File::create(format!("{}.apng", args[0])).unwrap();
+    clip.save_apng(std::io::BufWriter::new(fl)).unwrap();
+}
+
+/// `rvg2png gif <file.rvg> [fps] [--global] [--dither]`: export the first
+/// model's animation, with one palette for every frame if `--global`.
+fn gif(args: &[String]) {
+    let settings = rvg::GifSettings {
+        global_palette: args.iter().any(|arg| arg == "--global"),
+        dither: args.iter().any(|arg| arg == "--dither"),
+    };
+    let args: Vec<&String> =
+        args.iter().filter(|arg| !arg.starts_with("--")).collect();
+    assert!(args.len() == 1 || args.len() == 2);
+    let fps = args.get(1).map_or(30.0, |fps| fps.parse().unwrap());
+    let graphic = load(args[0]);
+
+    let clip = rvg::render_clip(&graphic, 0, fps).unwrap();
+    let fl = File::create(format!("{}.gif", args[0])).unwrap();
+    clip.save_gif(std::io::BufWriter::new(fl), settings)
+        .unwrap();
+}
+
+/// `rvg2png y4m <file.rvg> [fps] [--444|--422]`: export the first model's
+/// animation as video, for encoders like ffmpeg.
+fn y4m(args: &[String]) {
+    let mut settings = rvg::Y4mSettings::default();
+    if args.iter().any(|arg| arg == "--444") {
+        settings.subsampling = rvg::Subsampling::C444;
+    } else if args.iter().any(|arg| arg == "--422") {
+        settings.subsampling = rvg::Subsampling::C422;
+    }
+    let args: Vec<&String> =
+        args.iter().filter(|arg| !arg.starts_with("--")).collect();
+    assert!(args.len() == 1 || args.len() == 2);
+    let fps = args.get(1).map_or(30.0, |fps| fps.parse().unwrap());
+    let graphic = load(args[0]);
+
+    let clip = rvg::render_clip(&graphic, 0, fps).unwrap();
+    let fl = File::create(format!("{}.y4m", args[0])).unwrap();
+    clip.save_y4m(std::io::BufWriter::new(fl), settings)
+        .unwrap();
+}
+
+/// `rvg2png frames <file.rvg> [fps]`: export the first model's animation as
+/// numbered PNG files.
+fn frames(args: &[String]) {
+    assert!(args.len() == 1 || args.len() == 2);
+    let fps = args.get(1).map_or(30.0, |fps| fps.parse().unwrap());
+    let graphic = load(&args[0]);
+
+    let clip = rvg::render_clip(&graphic, 0, fps).unwrap();
+    clip.save_png_sequence(|i| {
+        let fl = File::create(format!("{}.{:05}.png", args[0], i)).ok()?;
+        Some(std::io::BufWriter::new(fl))
+    })
+    .unwrap();
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("apng") => return apng(&args[2..]),
+        Some("gif") => return gif(&args[2..]),
+        Some("y4m") => return y4m(&args[2..]),
+        Some("frames") => return frames(&args[2..]),
+        _ => {}
+    }
+    assert!(args.len() == 2 || args.len() == 3);
+    let time_ms = args.get(2).map_or(0.0, |t| t.parse().unwrap());
+    let graphic = load(&args[1]);
 
     let model = &graphic.models[0];
     let mut raster =
         Raster::<Rgba8p>::with_clear(model.width as u32, model.height as u32);
 
-    rvg::render(&mut raster, &graphic, ());
+    rvg::render_at(&mut raster, &graphic, 0, time_ms, ()).unwrap();
 
     write_png(Raster::with_raster(&raster), &format!("{}.png", args[1]))
         .unwrap();
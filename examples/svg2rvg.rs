@@ -1,6 +1,9 @@
 //! Convert an SVG into an RVG.
 
-use rvg::{Graphic, GroupProperty, Model, PathOp};
+use rvg::{
+    Affine2, FillRule, Gradient, GradientShape, Graphic, GroupProperty,
+    JoinStyle, LineCap, Model, PathOp, Spread, TransformOp,
+};
 use std::io::Write;
 use usvg::{NodeKind, Paint, PathSegment};
 
@@ -15,19 +18,98 @@ pub fn search_add(pts: &mut Vec<f32>, pt: &[f64], vbw: f32, vbh: f32) -> u32 {
     }
     pts.push(pt.0);
     pts.push(pt.1);
-    return (pts.len() / stride) as u32 - 1;
+    (pts.len() / stride) as u32 - 1
+}
+
+/// Convert a linked SVG gradient into an RVG gradient, in the space of the
+/// scaled vertices, with its colors faded by `opacity`.
+fn gradient(
+    tree: &usvg::Tree,
+    id: &str,
+    bbox: Option<usvg::Rect>,
+    opacity: f64,
+    ww: f32,
+    hh: f32,
+) -> Option<Gradient> {
+    let node = tree.defs_by_id(id)?;
+    let kind = node.borrow();
+    let (shape, base) = match &*kind {
+        NodeKind::LinearGradient(g) => (
+            GradientShape::Linear {
+                start: [g.x1 as f32, g.y1 as f32],
+                end: [g.x2 as f32, g.y2 as f32],
+            },
+            &g.base,
+        ),
+        NodeKind::RadialGradient(g) => (
+            GradientShape::Radial {
+                center: [g.cx as f32, g.cy as f32],
+                radius: g.r.value() as f32,
+                focus: [g.fx as f32, g.fy as f32],
+            },
+            &g.base,
+        ),
+        _ => return None,
+    };
+    let t = base.transform;
+    let mut transform = Affine2::new([
+        t.a as f32, t.b as f32, t.c as f32, t.d as f32, t.e as f32, t.f as f32,
+    ]);
+    if base.units == usvg::Units::ObjectBoundingBox {
+        let bbox = bbox?;
+        transform = transform.then(Affine2::new([
+            bbox.width() as f32,
+            0.0,
+            0.0,
+            bbox.height() as f32,
+            bbox.x() as f32,
+            bbox.y() as f32,
+        ]));
+    }
+    Some(Gradient {
+        shape,
+        stops: base
+            .stops
+            .iter()
+            .map(|stop| {
+                let c = stop.color;
+                let alpha = stop.opacity.value() * opacity * 255.0;
+                (
+                    stop.offset.value() as f32,
+                    [c.red, c.green, c.blue, alpha as u8],
+                )
+            })
+            .collect(),
+        spread: match base.spread_method {
+            usvg::SpreadMethod::Pad => Spread::Pad,
+            usvg::SpreadMethod::Reflect => Spread::Reflect,
+            usvg::SpreadMethod::Repeat => Spread::Repeat,
+        },
+        transform: transform.then(Affine2::scale(ww, hh)),
+    })
+}
+
+/// Add a gradient to a list, unless it's already there, and get its index.
+fn add_gradient(gradients: &mut Vec<Gradient>, gradient: Gradient) -> u32 {
+    let index = gradients.iter().position(|g| *g == gradient);
+    index.unwrap_or_else(|| {
+        gradients.push(gradient);
+        gradients.len() - 1
+    }) as u32
 }
 
 /// Convert an SVG string into RVG byte data.
 fn rvg_from_svg<W: Write>(svg: &str, w: W) {
     let mut group = Vec::new();
     let mut groups = Vec::new();
+    let mut transforms = Vec::new();
+    let mut gradients = Vec::new();
 
     // Build a new RVG.
     let mut pts = vec![];
 
     // Simplify SVG with usvg.
-    let tree = usvg::Tree::from_str(&svg, &usvg::Options::default()).unwrap();
+    let tree = usvg::Tree::from_str(svg, &usvg::Options::default()).unwrap();
 
     // Render
     let mut iter = tree.root().descendants();
@@ -56,71 +138,139 @@ fn rvg_from_svg<W: Write>(svg: &str, w: W) {
 
                 // Fill Color if it exists.
                 if let Some(fill) = &path.fill {
-                    if let Paint::Color(c) = fill.paint {
-                        let alpha = (fill.opacity.value() * 255.0) as u8;
-                        properties.push(GroupProperty::FillColorRgba([
-                            c.red, c.green, c.blue, alpha,
-                        ]));
-                    } else {
-                        panic!("Linked paint server not supported!");
-                    };
+                    match fill.paint {
+                        Paint::Color(c) => {
+                            let alpha = (fill.opacity.value() * 255.0) as u8;
+                            properties.push(GroupProperty::FillColorRgba([
+                                c.red, c.green, c.blue, alpha,
+                            ]));
+                        }
+                        Paint::Link(ref id) => {
+                            let g = gradient(
+                                &tree,
+                                id,
+                                path.data.bbox(),
+                                fill.opacity.value(),
+                                ww,
+                                hh,
+                            )
+                            .expect("Linked paint server not supported!");
+                            let i = add_gradient(&mut gradients, g);
+                            properties.push(GroupProperty::FillGradient(i));
+                        }
+                    }
+                    if fill.rule == usvg::FillRule::EvenOdd {
+                        properties
+                            .push(GroupProperty::FillRule(FillRule::EvenOdd));
+                    }
                 }
 
                 // Stroke Width & Color
                 if let Some(stroke) = &path.stroke {
                     // Color
-                    if let Paint::Color(c) = stroke.paint {
-                        properties.push(GroupProperty::StrokeColorRgba([
-                            c.red,
-                            c.green,
-                            c.blue,
-                            (stroke.opacity.value() * 255.0) as u8,
-                        ]));
-                    } else {
-                        panic!("Linked paint server not supported!");
-                    };
+                    match stroke.paint {
+                        Paint::Color(c) => {
+                            properties.push(GroupProperty::StrokeColorRgba([
+                                c.red,
+                                c.green,
+                                c.blue,
+                                (stroke.opacity.value() * 255.0) as u8,
+                            ]));
+                        }
+                        Paint::Link(ref id) => {
+                            let g = gradient(
+                                &tree,
+                                id,
+                                path.data.bbox(),
+                                stroke.opacity.value(),
+                                ww,
+                                hh,
+                            )
+                            .expect("Linked paint server not supported!");
+                            let i = add_gradient(&mut gradients, g);
+                            properties.push(GroupProperty::StrokeGradient(i));
+                        }
+                    }
 
                     properties.push(GroupProperty::StrokeWidth(
                         stroke.width.value() as f32 * ww,
                     ));
+
+                    // Joins & Caps
+                    match stroke.linejoin {
+                        usvg::LineJoin::Miter => {}
+                        usvg::LineJoin::Round => properties
+                            .push(GroupProperty::JoinStyle(JoinStyle::Round)),
+                        usvg::LineJoin::Bevel => properties
+                            .push(GroupProperty::JoinStyle(JoinStyle::Bevel)),
+                    }
+                    match stroke.linecap {
+                        usvg::LineCap::Butt => {}
+                        usvg::LineCap::Round => properties
+                            .push(GroupProperty::LineCap(LineCap::Round)),
+                        usvg::LineCap::Square => properties
+                            .push(GroupProperty::LineCap(LineCap::Square)),
+                    }
+                    let limit = stroke.miterlimit.value() as f32;
+                    if limit != 4.0 {
+                        properties.push(GroupProperty::MiterLimit(limit));
+                    }
+
+                    // Dashes
+                    if let Some(ref dasharray) = stroke.dasharray {
+                        properties.push(GroupProperty::Dash {
+                            pattern: dasharray
+                                .iter()
+                                .map(|length| *length as f32 * ww)
+                                .collect(),
+                            offset: stroke.dashoffset * ww,
+                        });
+                    }
                 }
 
-                // Get transform
-                let transform = path.transform;
+                // Keep the transform, in the space of the scaled vertices.
+                let t = path.transform;
+                transforms.push(if t.is_default() {
+                    vec![]
+                } else {
+                    vec![TransformOp::Matrix([
+                        t.a as f32,
+                        t.b as f32 * hh / ww,
+                        t.c as f32 * ww / hh,
+                        t.d as f32,
+                        t.e as f32 * ww,
+                        t.f as f32 * hh,
+                    ])]
+                });
 
                 let mut pathops = vec![];
 
                 for subpath in path.data.subpaths() {
                     for segment in subpath.0 {
                         match *segment {
-                            PathSegment::MoveTo { mut x, mut y } => {
-                                transform.apply_to(&mut x, &mut y);
+                            PathSegment::MoveTo { x, y } => {
                                 let i = search_add(&mut pts, &[x, y], ww, hh);
                                 pathops.push(PathOp::Move(i));
                             }
-                            PathSegment::LineTo { mut x, mut y } => {
-                                transform.apply_to(&mut x, &mut y);
+                            PathSegment::LineTo { x, y } => {
                                 let i = search_add(&mut pts, &[x, y], ww, hh);
                                 pathops.push(PathOp::Line(i));
                             }
                             PathSegment::CurveTo {
-                                mut x1,
-                                mut y1,
-                                mut x2,
-                                mut y2,
-                                mut x,
-                                mut y,
+                                x1,
+                                y1,
+                                x2,
+                                y2,
+                                x,
+                                y,
                             } => {
-                                transform.apply_to(&mut x1, &mut y1);
-                                transform.apply_to(&mut x2, &mut y2);
-                                transform.apply_to(&mut x, &mut y);
                                 let i = search_add(&mut pts, &[x1, y1], ww, hh);
                                 let j = search_add(&mut pts, &[x2, y2], ww, hh);
                                 let k = search_add(&mut pts, &[x, y], ww, hh);
                                 dbg!(k);
                                 pathops.push(PathOp::Cubic(i, j, k));
                             }
-                            PathSegment::ClosePath {} => {
+                            PathSegment::ClosePath => {
                                 pathops.push(PathOp::Close());
                             }
                         }
@@ -143,15 +293,19 @@ fn rvg_from_svg<W: Write>(svg: &str, w: W) {
         attributes: Vec::new(), // Don't use any attributes
         vertex_list: pts,
         group,
+        gradients,
         models: vec![Model {
             width,
             height,
             groups,
             frames: vec![rvg::Frame {
-                transforms: Vec::new(),
+                transforms,
+                properties: Vec::new(),
+                morph: rvg::Morph::None,
                 delay: 0,
                 animation: rvg::Animation::Done,
             }],
+            playback: Default::default(),
         }],
         bitmaps: Vec::new(),
     };
@@ -164,6 +318,6 @@ fn main() {
     let svg = std::fs::read_to_string(&args[1]).unwrap();
 
     let fl = std::fs::File::create(format!("{}.rvg", args[1])).unwrap();
-    let ref mut bw = std::io::BufWriter::new(fl);
+    let bw = &mut std::io::BufWriter::new(fl);
     rvg_from_svg(&svg, bw);
 }
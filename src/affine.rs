@@ -0,0 +1,302 @@
+use crate::{Graphic, TransformOp};
+use std::ops::Mul;
+
+/// A 2D affine transformation matrix.
+///
+/// Maps (x, y) to (a·x + c·y + e, b·x + d·y + f), the same layout as SVG's
+/// `matrix(a b c d e f)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Affine2 {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Default for Affine2 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// `lhs * rhs` applies `rhs` first, then `lhs`.
+impl Mul for Affine2 {
+    type Output = Affine2;
+
+    fn mul(self, rhs: Affine2) -> Affine2 {
+        Affine2 {
+            a: self.a * rhs.a + self.c * rhs.b,
+            b: self.b * rhs.a + self.d * rhs.b,
+            c: self.a * rhs.c + self.c * rhs.d,
+            d: self.b * rhs.c + self.d * rhs.d,
+            e: self.a * rhs.e + self.c * rhs.f + self.e,
+            f: self.b * rhs.e + self.d * rhs.f + self.f,
+        }
+    }
+}
+
+impl Affine2 {
+    /// The transform that leaves points where they are.
+    pub const IDENTITY: Affine2 = Affine2 {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 1.0,
+        e: 0.0,
+        f: 0.0,
+    };
+
+    /// Create a matrix from its six values, in SVG order.
+    pub fn new(m: [f32; 6]) -> Self {
+        Affine2 {
+            a: m[0],
+            b: m[1],
+            c: m[2],
+            d: m[3],
+            e: m[4],
+            f: m[5],
+        }
+    }
+
+    /// Get the six values of the matrix, in SVG order.
+    pub fn to_array(self) -> [f32; 6] {
+        [self.a, self.b, self.c, self.d, self.e, self.f]
+    }
+
+    /// Create a translation.
+    pub fn translate(x: f32, y: f32) -> Self {
+        Self::new([1.0, 0.0, 0.0, 1.0, x, y])
+    }
+
+    /// Create a scale about the origin.
+    pub fn scale(x: f32, y: f32) -> Self {
+        Self::new([x, 0.0, 0.0, y, 0.0, 0.0])
+    }
+
+    /// Create a rotation about the origin, by an angle in radians.
+    pub fn rotate(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        Self::new([c, s, -s, c, 0.0, 0.0])
+    }
+
+    /// Create a skew, by angles in radians along the X and Y axes.
+    pub fn skew(x: f32, y: f32) -> Self {
+        Self::new([1.0, y.tan(), x.tan(), 1.0, 0.0, 0.0])
+    }
+
+    /// Compose the operations of a transform list, applied in order.
+    pub fn from_ops(ops: &[TransformOp]) -> Self {
+        ops.iter()
+            .fold(Self::IDENTITY, |matrix, op| op.to_matrix() * matrix)
+    }
+
+    /// Compose the operations of a transform list, applied in order, with
+    /// motion paths following the paths of a graphic.
+    pub fn from_ops_in(ops: &[TransformOp], graphic: &Graphic) -> Self {
+        Self::from_ops_along(ops, |path, distance| {
+            graphic.point_along(path, distance)
+        })
+    }
+
+    /// Compose the operations of a transform list, applied in order, with
+    /// `along` giving the point and tangent at a distance along a path.
+    pub(crate) fn from_ops_along<F>(ops: &[TransformOp], along: F) -> Self
+    where
+        F: Fn(u32, f32) -> Option<([f32; 2], [f32; 2])>,
+    {
+        ops.iter().fold(Self::IDENTITY, |matrix, op| {
+            op.to_matrix_along(&along) * matrix
+        })
+    }
+
+    /// Get the same transform, acting about the point (x, y) rather than
+    /// the origin.
+    pub fn about(self, x: f32, y: f32) -> Self {
+        Self::translate(x, y) * self * Self::translate(-x, -y)
+    }
+
+    /// Apply `self`, and then `next`.
+    pub fn then(self, next: Affine2) -> Self {
+        next * self
+    }
+
+    /// Get the determinant of the linear part of the matrix.
+    pub fn determinant(self) -> f32 {
+        self.a * self.d - self.b * self.c
+    }
+
+    /// Get the inverse transform, if the matrix is not singular.
+    pub fn inverse(self) -> Option<Self> {
+        let det = self.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let (a, b, c, d) =
+            (self.d / det, -self.b / det, -self.c / det, self.a / det);
+        Some(Affine2 {
+            a,
+            b,
+            c,
+            d,
+            e: -(a * self.e + c * self.f),
+            f: -(b * self.e + d * self.f),
+        })
+    }
+
+    /// Map a point through the transform.
+    pub fn apply(self, x: f32, y: f32) -> (f32, f32) {
+        (
+            self.a * x + self.c * y + self.e,
+            self.b * x + self.d * y + self.f,
+        )
+    }
+
+    /// Map a vector through the transform, ignoring translation.
+    pub fn apply_vector(self, x: f32, y: f32) -> (f32, f32) {
+        (self.a * x + self.c * y, self.b * x + self.d * y)
+    }
+}
+
+impl TransformOp {
+    /// Get the 2D matrix for this operation, following motion paths in the
+    /// paths of a graphic.
+    pub fn to_matrix_in(&self, graphic: &Graphic) -> Affine2 {
+        self.to_matrix_along(|path, distance| {
+            graphic.point_along(path, distance)
+        })
+    }
+
+    /// Get the 2D matrix for this operation, with `along` giving the point
+    /// and tangent at a distance along a path.
+    pub(crate) fn to_matrix_along<F>(self, along: F) -> Affine2
+    where
+        F: Fn(u32, f32) -> Option<([f32; 2], [f32; 2])>,
+    {
+        match self {
+            TransformOp::MotionPath(path, distance, rotate) => {
+                let ([x, y], [tx, ty]) = match along(path, distance) {
+                    Some(point) => point,
+                    None => return Affine2::IDENTITY,
+                };
+                let matrix = Affine2::translate(x, y);
+                if rotate {
+                    matrix * Affine2::rotate(ty.atan2(tx))
+                } else {
+                    matrix
+                }
+            }
+            _ => self.to_matrix(),
+        }
+    }
+
+    /// Get the 2D matrix for this operation.  Depth is dropped, so 3D
+    /// rotations are projected onto the z = 0 plane.  Motion paths need the
+    /// graphic (see [`to_matrix_in`](#method.to_matrix_in)), so they are the
+    /// identity here.
+    pub fn to_matrix(&self) -> Affine2 {
+        match *self {
+            TransformOp::Translate(x, y, _z) => Affine2::translate(x, y),
+            TransformOp::Scale(x, y, _z) => Affine2::scale(x, y),
+            TransformOp::Rotate(vx, vy, vz, rot) => {
+                let len = (vx * vx + vy * vy + vz * vz).sqrt();
+                let (kx, ky, kz) = if len == 0.0 {
+                    (0.0, 0.0, 1.0)
+                } else {
+                    (vx / len, vy / len, vz / len)
+                };
+                let (s, c) = rot.sin_cos();
+                let t = 1.0 - c;
+                Affine2::new([
+                    c + t * kx * kx,
+                    t * kx * ky + s * kz,
+                    t * kx * ky - s * kz,
+                    c + t * ky * ky,
+                    0.0,
+                    0.0,
+                ])
+            }
+            TransformOp::Skew(x, y) => Affine2::skew(x, y),
+            TransformOp::Matrix(m) => Affine2::new(m),
+            TransformOp::MotionPath(..) => Affine2::IDENTITY,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_near(a: Affine2, b: Affine2) {
+        for (x, y) in a.to_array().iter().zip(&b.to_array()) {
+            assert!((x - y).abs() < 1e-5, "{:?} is not {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn then_applies_in_order() {
+        let m = Affine2::translate(1.0, 0.0).then(Affine2::scale(2.0, 3.0));
+        assert_eq!(m.apply(1.0, 1.0), (4.0, 3.0));
+        let m = Affine2::scale(2.0, 3.0).then(Affine2::translate(1.0, 0.0));
+        assert_eq!(m.apply(1.0, 1.0), (3.0, 3.0));
+        assert_eq!(m.apply_vector(1.0, 1.0), (2.0, 3.0));
+    }
+
+    #[test]
+    fn inverse() {
+        let m = Affine2::new([2.0, 1.0, -1.0, 3.0, 5.0, -7.0]);
+        let inv = m.inverse().unwrap();
+        assert_near(m.then(inv), Affine2::IDENTITY);
+        assert_near(inv.then(m), Affine2::IDENTITY);
+        assert!(Affine2::scale(0.0, 1.0).inverse().is_none());
+        assert!(Affine2::new([1.0, 2.0, 2.0, 4.0, 0.0, 0.0])
+            .inverse()
+            .is_none());
+        assert!(Affine2::scale(f32::INFINITY, 1.0).inverse().is_none());
+    }
+
+    #[test]
+    fn ops_to_matrix() {
+        let ops = [
+            TransformOp::Scale(2.0, 2.0, 1.0),
+            TransformOp::Rotate(0.0, 0.0, 1.0, FRAC_PI_2),
+            TransformOp::Translate(10.0, 0.0, 0.0),
+        ];
+        let m = Affine2::from_ops(&ops);
+        let (x, y) = m.apply(1.0, 0.0);
+        assert!((x - 10.0).abs() < 1e-5 && (y - 2.0).abs() < 1e-5);
+        assert_eq!(Affine2::from_ops(&[]), Affine2::IDENTITY);
+
+        // A rotation about the negative z axis turns the other way.
+        assert_near(
+            TransformOp::Rotate(0.0, 0.0, -2.0, FRAC_PI_2).to_matrix(),
+            Affine2::rotate(-FRAC_PI_2),
+        );
+        // Flipping about the x axis mirrors y.
+        assert_near(
+            TransformOp::Rotate(1.0, 0.0, 0.0, std::f32::consts::PI)
+                .to_matrix(),
+            Affine2::scale(1.0, -1.0),
+        );
+        assert_near(
+            TransformOp::Skew(FRAC_PI_2 / 2.0, 0.0).to_matrix(),
+            Affine2::new([1.0, 0.0, 1.0, 1.0, 0.0, 0.0]),
+        );
+        let m = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        assert_eq!(TransformOp::Matrix(m).to_matrix().to_array(), m);
+    }
+
+    #[test]
+    fn about_pivot() {
+        let m = Affine2::rotate(FRAC_PI_2).about(2.0, 1.0);
+        let (x, y) = m.apply(2.0, 1.0);
+        assert!((x - 2.0).abs() < 1e-5 && (y - 1.0).abs() < 1e-5);
+        let (x, y) = m.apply(3.0, 1.0);
+        assert!((x - 2.0).abs() < 1e-5 && (y - 2.0).abs() < 1e-5);
+        let m = Affine2::scale(2.0, 2.0).about(1.0, 1.0);
+        assert_eq!(m.apply(0.0, 0.0), (-1.0, -1.0));
+        assert_eq!(Affine2::translate(3.0, 4.0).about(9.0, 9.0).e, 3.0);
+    }
+}
@@ -0,0 +1,979 @@
+use crate::{
+    Affine2, Animation, ColorSpace, Graphic, GroupProperty, Model,
+    StepPosition, TransformOp,
+};
+use std::collections::BTreeSet;
+
+/// The state of every group of a model at one point in time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pose {
+    /// Transform of each group (including its pivot, and composed with the
+    /// transforms of its parents), in the order of `Model::groups`
+    pub transforms: Vec<Affine2>,
+    /// Animatable properties of each group, in the order of `Model::groups`
+    pub styles: Vec<Style>,
+    /// Positions (x, y) of the vertices moved by keyframe morphs, sorted by
+    /// vertex index.  Other vertices stay where `Graphic::vertex_list` has
+    /// them.
+    pub vertices: Vec<(u32, [f32; 2])>,
+}
+
+/// The animatable properties of a group.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Style {
+    /// Fill color (sRGBA)
+    pub fill: [u8; 4],
+    /// Stroke color (sRGBA)
+    pub stroke: [u8; 4],
+    /// Stroke width
+    pub stroke_width: f32,
+    /// Opacity (0 to 1)
+    pub opacity: f32,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            fill: [0, 0, 0, 0],
+            stroke: [0, 0, 0, 0],
+            stroke_width: 1.0,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl Style {
+    /// Apply the animatable properties in a property list.
+    fn apply(mut self, props: &[GroupProperty]) -> Self {
+        for prop in props {
+            match *prop {
+                GroupProperty::FillColorRgba(color) => self.fill = color,
+                GroupProperty::StrokeColorRgba(color) => self.stroke = color,
+                GroupProperty::StrokeWidth(width) => self.stroke_width = width,
+                GroupProperty::Opacity(opacity) => self.opacity = opacity,
+                _ => {}
+            }
+        }
+        self
+    }
+
+    /// Interpolate between two styles.
+    fn lerp(self, other: Style, t: f32, space: ColorSpace) -> Self {
+        if t == 0.0 {
+            return self;
+        }
+        Style {
+            fill: lerp_color(self.fill, other.fill, t, space),
+            stroke: lerp_color(self.stroke, other.stroke, t, space),
+            stroke_width: lerp(self.stroke_width, other.stroke_width, t),
+            opacity: lerp(self.opacity, other.opacity, t),
+        }
+    }
+}
+
+/// Convert an sRGB gamma-encoded channel (0 to 1) to linear light.
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Convert a linear light channel (0 to 1) to sRGB gamma encoding.
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Interpolate between two sRGBA colors with premultiplied alpha.
+fn lerp_color(a: [u8; 4], b: [u8; 4], t: f32, space: ColorSpace) -> [u8; 4] {
+    let decode = |c: u8| {
+        let c = f32::from(c) / 255.0;
+        match space {
+            ColorSpace::Srgb => c,
+            ColorSpace::Linear => srgb_to_linear(c),
+        }
+    };
+    let (aa, ba) = (f32::from(a[3]) / 255.0, f32::from(b[3]) / 255.0);
+    let alpha = lerp(aa, ba, t);
+    let mut out = [0, 0, 0, (alpha * 255.0).round() as u8];
+    if alpha > 0.0 {
+        for i in 0..3 {
+            let c = lerp(decode(a[i]) * aa, decode(b[i]) * ba, t) / alpha;
+            let c = match space {
+                ColorSpace::Srgb => c,
+                ColorSpace::Linear => linear_to_srgb(c),
+            };
+            out[i] = (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+    }
+    out
+}
+
+/// A model sampled at one point in time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelState {
+    /// Index of the keyframe being shown, or transitioned away from
+    pub frame: usize,
+    /// Eased progress (0 to 1) of the transition to the next keyframe
+    pub progress: f32,
+    /// Poses left on the canvas by earlier `Animation::Layer` frames, to be
+    /// drawn bottom to top before `pose`
+    pub layers: Vec<Pose>,
+    /// The pose to draw
+    pub pose: Pose,
+    /// The pose an `Animation::Fade` frame is cross-fading into, and how much
+    /// of it (0 to 1) to blend over `pose`
+    pub fade: Option<(Pose, f32)>,
+}
+
+impl Animation {
+    /// Map linear progress (0 to 1) through a transition to the amount of
+    /// change from one keyframe to the next.
+    ///
+    /// `ExpA(k)` and `ExpB(k)` are symmetric power curves: `ExpB` follows
+    /// (2p)ᵏ / 2 over the first half (slow ends when k > 1), and `ExpA` is its
+    /// inverse shape (fast ends).  A k of 1 is linear.  `CubicBezier` and
+    /// `Steps` follow the CSS timing functions of the same names.
+    pub fn ease(&self, progress: f32) -> f32 {
+        let p = progress.clamp(0.0, 1.0);
+        match *self {
+            Animation::Done | Animation::Jump | Animation::Layer => 0.0,
+            Animation::Linear | Animation::Fade => p,
+            Animation::ExpA(k) => {
+                if p < 0.5 {
+                    0.5 - 0.5 * (1.0 - 2.0 * p).powf(k)
+                } else {
+                    0.5 + 0.5 * (2.0 * p - 1.0).powf(k)
+                }
+            }
+            Animation::ExpB(k) => {
+                if p < 0.5 {
+                    0.5 * (2.0 * p).powf(k)
+                } else {
+                    1.0 - 0.5 * (2.0 - 2.0 * p).powf(k)
+                }
+            }
+            Animation::CubicBezier(x1, y1, x2, y2) => {
+                cubic_bezier(p, x1.clamp(0.0, 1.0), y1, x2.clamp(0.0, 1.0), y2)
+            }
+            Animation::Steps(steps, position) => {
+                let steps = steps.max(1) as f32;
+                let (jumps, step) = match position {
+                    StepPosition::JumpStart => {
+                        (steps, (p * steps).floor() + 1.0)
+                    }
+                    StepPosition::JumpEnd => (steps, (p * steps).floor()),
+                    StepPosition::JumpNone => {
+                        (steps - 1.0, (p * steps).floor())
+                    }
+                    StepPosition::JumpBoth => {
+                        (steps + 1.0, (p * steps).floor() + 1.0)
+                    }
+                };
+                if jumps <= 0.0 {
+                    return if p >= 1.0 { 1.0 } else { 0.0 };
+                }
+                step.min(jumps) / jumps
+            }
+        }
+    }
+}
+
+/// Evaluate a CSS `cubic-bezier()` timing function, with the curve running
+/// from (0, 0) to (1, 1) through control points (x1, y1) and (x2, y2).
+fn cubic_bezier(x: f32, x1: f32, y1: f32, x2: f32, y2: f32) -> f32 {
+    // Polynomial coefficients of each axis.
+    let (cx, cy) = (3.0 * x1, 3.0 * y1);
+    let (bx, by) = (3.0 * (x2 - x1) - cx, 3.0 * (y2 - y1) - cy);
+    let (ax, ay) = (1.0 - cx - bx, 1.0 - cy - by);
+    let curve_x = |t: f32| ((ax * t + bx) * t + cx) * t;
+    let curve_y = |t: f32| ((ay * t + by) * t + cy) * t;
+    let slope_x = |t: f32| (3.0 * ax * t + 2.0 * bx) * t + cx;
+
+    // Solve for the parameter at `x` with Newton's method, falling back to
+    // bisection (x is monotonic in t, as x1 and x2 are within 0 to 1).
+    let mut t = x;
+    for _ in 0..8 {
+        let error = curve_x(t) - x;
+        if error.abs() < 1e-6 {
+            return curve_y(t);
+        }
+        let slope = slope_x(t);
+        if slope.abs() < 1e-6 {
+            break;
+        }
+        t -= error / slope;
+    }
+    let (mut lo, mut hi) = (0.0, 1.0);
+    t = x;
+    for _ in 0..32 {
+        let error = curve_x(t) - x;
+        if error.abs() < 1e-6 {
+            break;
+        }
+        if error > 0.0 {
+            hi = t;
+        } else {
+            lo = t;
+        }
+        t = (lo + hi) * 0.5;
+    }
+    curve_y(t)
+}
+
+/// Linearly interpolate between two numbers.
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Interpolate between two transform operations of the same kind.
+fn lerp_op(a: &TransformOp, b: &TransformOp, t: f32) -> Option<TransformOp> {
+    use TransformOp::*;
+    Some(match (*a, *b) {
+        (Translate(ax, ay, az), Translate(bx, by, bz)) => {
+            Translate(lerp(ax, bx, t), lerp(ay, by, t), lerp(az, bz, t))
+        }
+        (Scale(ax, ay, az), Scale(bx, by, bz)) => {
+            Scale(lerp(ax, bx, t), lerp(ay, by, t), lerp(az, bz, t))
+        }
+        (Rotate(ax, ay, az, ar), Rotate(bx, by, bz, br)) => Rotate(
+            lerp(ax, bx, t),
+            lerp(ay, by, t),
+            lerp(az, bz, t),
+            lerp(ar, br, t),
+        ),
+        (Skew(ax, ay), Skew(bx, by)) => Skew(lerp(ax, bx, t), lerp(ay, by, t)),
+        (Matrix(a), Matrix(b)) => {
+            let mut m = a;
+            for (m, b) in m.iter_mut().zip(b.iter()) {
+                *m = lerp(*m, *b, t);
+            }
+            Matrix(m)
+        }
+        (MotionPath(ap, ad, ar), MotionPath(bp, bd, br))
+            if ap == bp && ar == br =>
+        {
+            MotionPath(ap, lerp(ad, bd, t), ar)
+        }
+        _ => return None,
+    })
+}
+
+/// Interpolate between two transform lists.  Lists made of the same kinds of
+/// operations are interpolated operation by operation, others by matrix.
+/// Motion paths follow `along`, the point and tangent at a distance along a
+/// path.
+fn lerp_ops<F>(
+    along: F,
+    a: &[TransformOp],
+    b: &[TransformOp],
+    t: f32,
+) -> Affine2
+where
+    F: Fn(u32, f32) -> Option<([f32; 2], [f32; 2])>,
+{
+    let from_ops = |ops: &[TransformOp]| Affine2::from_ops_along(ops, &along);
+    if t == 0.0 {
+        return from_ops(a);
+    }
+    if a.len() == b.len() {
+        let ops: Option<Vec<_>> =
+            a.iter().zip(b).map(|(a, b)| lerp_op(a, b, t)).collect();
+        if let Some(ops) = ops {
+            return from_ops(&ops);
+        }
+    }
+    let (a, b) = (from_ops(a), from_ops(b));
+    let mut m = a.to_array();
+    for (m, b) in m.iter_mut().zip(b.to_array().iter()) {
+        *m = lerp(*m, *b, t);
+    }
+    Affine2::new(m)
+}
+
+impl Model {
+    /// Get the sum of the frame delays in milliseconds.
+    fn timeline_duration(&self) -> u32 {
+        self.frames.iter().map(|frame| u32::from(frame.delay)).sum()
+    }
+
+    /// Get the length of one loop of the timeline in milliseconds.
+    pub fn duration(&self) -> u32 {
+        match self.playback.duration {
+            0 => self.timeline_duration(),
+            duration => duration,
+        }
+    }
+
+    /// Get the length of the whole animation in milliseconds, including the
+    /// start delay and every loop, or `None` if it loops forever.
+    pub fn total_duration(&self) -> Option<u64> {
+        match self.playback.loops {
+            0 => None,
+            loops => Some(
+                u64::from(self.playback.start_delay)
+                    + u64::from(loops) * u64::from(self.duration()),
+            ),
+        }
+    }
+
+    /// Map a time since the start of playback to a time on the timeline of
+    /// frame delays, following the `Playback` settings.
+    fn timeline_time(&self, t_ms: f32) -> f32 {
+        let t = t_ms - self.playback.start_delay as f32;
+        let duration = self.duration() as f32;
+        if t <= 0.0 || duration <= 0.0 {
+            return 0.0;
+        }
+        let scale = self.timeline_duration() as f32 / duration;
+        let mut iteration = (t / duration).floor();
+        let mut local = t - iteration * duration;
+        let loops = self.playback.loops;
+        if loops != 0 && iteration >= loops as f32 {
+            // Hold the end of the last loop.
+            iteration = (loops - 1) as f32;
+            local = duration;
+        }
+        if self.playback.ping_pong && iteration % 2.0 == 1.0 {
+            local = duration - local;
+        }
+        local * scale
+    }
+
+    /// Get the animatable properties of a group, by its index in `groups`,
+    /// before any frame overrides them.
+    pub fn style(&self, group: usize) -> Style {
+        let props = self.groups.get(group).map_or(&[][..], |(_, p)| p);
+        Style::default().apply(props)
+    }
+
+    /// Get the pose of a keyframe, eased `t` of the way to the next one.
+    /// Vertex morphs and motion paths need the graphic's vertices.
+    fn pose(&self, graphic: Option<&Graphic>, frame: usize, t: f32) -> Pose {
+        let next = (frame + 1).min(self.frames.len().max(1) - 1);
+        let ops = |frame: usize, group: usize| {
+            self.frames
+                .get(frame)
+                .and_then(|frame| frame.transforms.get(group))
+                .map_or(&[][..], |ops| ops)
+        };
+        let style = |frame: usize, group: usize| {
+            let props = self
+                .frames
+                .get(frame)
+                .and_then(|frame| frame.properties.get(group))
+                .map_or(&[][..], |props| props);
+            self.style(group).apply(props)
+        };
+        let vertices = match graphic {
+            Some(graphic) => self.morph(graphic, frame, next, t),
+            None => Vec::new(),
+        };
+        let position = |index: u32| match vertices
+            .binary_search_by_key(&index, |(index, _)| *index)
+        {
+            Ok(i) => Some(vertices[i].1),
+            Err(_) => graphic?.vertex(index),
+        };
+        let route = |path: u32, distance: f32| {
+            graphic?.point_along_with(path, distance, position)
+        };
+        let local = |group: usize, along: &dyn Fn(u32, f32) -> _| {
+            let (x, y) = self.pivot(group);
+            lerp_ops(along, ops(frame, group), ops(next, group), t).about(x, y)
+        };
+
+        // Routes are followed where the first group drawing them puts them,
+        // in the space of the moving group's parent.
+        let mut locals: Vec<Affine2> = (0..self.groups.len())
+            .map(|group| local(group, &route))
+            .collect();
+        let drawn: Vec<Affine2> = (0..self.groups.len())
+            .map(|group| self.compose(&locals, group))
+            .collect();
+        for (group, matrix) in locals.iter_mut().enumerate() {
+            let moves = ops(frame, group)
+                .iter()
+                .chain(ops(next, group))
+                .any(|op| matches!(op, TransformOp::MotionPath(..)));
+            if !moves {
+                continue;
+            }
+            let to_parent = self
+                .parent(group)
+                .and_then(|parent| drawn[parent].inverse())
+                .unwrap_or(Affine2::IDENTITY);
+            let along = |path: u32, distance: f32| {
+                let ([x, y], [tx, ty]) = route(path, distance)?;
+                let m = self
+                    .groups
+                    .iter()
+                    .position(|(id, _)| *id == path)
+                    .map_or(Affine2::IDENTITY, |drawing| drawn[drawing])
+                    .then(to_parent);
+                let ((x, y), (tx, ty)) =
+                    (m.apply(x, y), m.apply_vector(tx, ty));
+                Some(([x, y], [tx, ty]))
+            };
+            *matrix = local(group, &along);
+        }
+        let transforms = (0..self.groups.len())
+            .map(|group| self.compose(&locals, group))
+            .collect();
+        let space = self.playback.color_space;
+        let styles = (0..self.groups.len())
+            .map(|group| style(frame, group).lerp(style(next, group), t, space))
+            .collect();
+        Pose {
+            transforms,
+            styles,
+            vertices,
+        }
+    }
+
+    /// Compose a group's transform with the transforms of its ancestors.
+    /// Groups in a cycle of parents ignore their ancestors.
+    fn compose(&self, local: &[Affine2], group: usize) -> Affine2 {
+        let mut matrix = local[group];
+        let mut child = group;
+        for _ in 0..local.len() {
+            match self.parent(child) {
+                Some(parent) => {
+                    matrix = local[parent] * matrix;
+                    child = parent;
+                }
+                None => return matrix,
+            }
+        }
+        local[group]
+    }
+
+    /// Get the positions of the vertices morphed by a keyframe, eased `t`
+    /// of the way to the next one.
+    fn morph(
+        &self,
+        graphic: &Graphic,
+        frame: usize,
+        next: usize,
+        t: f32,
+    ) -> Vec<(u32, [f32; 2])> {
+        let morph = |frame: usize| self.frames.get(frame).map(|f| &f.morph);
+        let (a, b) = match (morph(frame), morph(next)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return Vec::new(),
+        };
+        let mut indices: BTreeSet<u32> = a.indices().into_iter().collect();
+        if t != 0.0 {
+            indices.extend(b.indices());
+        }
+        indices
+            .into_iter()
+            .filter_map(|index| {
+                let base = graphic.vertex(index);
+                let [ax, ay] = a.position(index).or(base)?;
+                let [bx, by] = b.position(index).or(base)?;
+                Some((index, [lerp(ax, bx, t), lerp(ay, by, t)]))
+            })
+            .collect()
+    }
+
+    /// Sample the model at a time in milliseconds from the start of its
+    /// playback.  Before the start, the first keyframe is shown, and after
+    /// the last loop, the keyframe it ends on.
+    ///
+    /// Vertex morphs and motion paths are left out, as they need the
+    /// graphic's vertices; use
+    /// [`Graphic::sample`](struct.Graphic.html#method.sample) for those.
+    pub fn sample(&self, t_ms: f32) -> ModelState {
+        self.sample_with(None, t_ms)
+    }
+
+    /// Sample the model, morphing the vertices and following the paths of a
+    /// graphic if there is one.
+    fn sample_with(&self, graphic: Option<&Graphic>, t_ms: f32) -> ModelState {
+        let t_ms = self.timeline_time(t_ms);
+
+        // Find the keyframe that the time falls in.
+        let mut frame = 0;
+        let mut start = 0.0;
+        let last = self.frames.len().max(1) - 1;
+        while frame < last {
+            let delay = f32::from(self.frames[frame].delay);
+            if t_ms < start + delay {
+                break;
+            }
+            start += delay;
+            frame += 1;
+        }
+
+        let (animation, linear) = match self.frames.get(frame) {
+            Some(f) if frame < last => {
+                let delay = f32::from(f.delay);
+                (&f.animation, (t_ms - start).max(0.0) / delay)
+            }
+            _ => (&Animation::Done, 0.0),
+        };
+        let progress = animation.ease(linear);
+
+        let (pose, fade) = match animation {
+            Animation::Fade => (
+                self.pose(graphic, frame, 0.0),
+                Some((self.pose(graphic, frame + 1, 0.0), progress)),
+            ),
+            _ => (self.pose(graphic, frame, progress), None),
+        };
+
+        // Frames are left on the canvas by each `Layer` frame leading up to
+        // this one.
+        let mut first = frame;
+        while first > 0 && self.frames[first - 1].animation == Animation::Layer
+        {
+            first -= 1;
+        }
+        let layers =
+            (first..frame).map(|i| self.pose(graphic, i, 0.0)).collect();
+
+        ModelState {
+            frame,
+            progress,
+            layers,
+            pose,
+            fade,
+        }
+    }
+}
+
+impl Graphic {
+    /// Sample one of the graphic's models at a time in milliseconds from the
+    /// start of its playback, like
+    /// [`Model::sample`](struct.Model.html#method.sample), including vertex
+    /// morphs and motion paths.
+    pub fn sample(&self, model: usize, t_ms: f32) -> Option<ModelState> {
+        Some(self.models.get(model)?.sample_with(Some(self), t_ms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Frame, GroupProperty, Morph, PathOp, Playback};
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} is not {}", a, b);
+    }
+
+    fn frame(x: f32, delay: u16, animation: Animation) -> Frame {
+        Frame {
+            transforms: vec![vec![TransformOp::Translate(x, 0.0, 0.0)]],
+            properties: vec![],
+            morph: Morph::None,
+            delay,
+            animation,
+        }
+    }
+
+    fn model(frames: Vec<Frame>) -> Model {
+        Model {
+            width: 10.0,
+            height: 10.0,
+            groups: vec![(0, vec![])],
+            frames,
+            playback: Playback::default(),
+        }
+    }
+
+    /// Get the x translation of the only group.
+    fn x(pose: &Pose) -> f32 {
+        pose.transforms[0].e
+    }
+
+    #[test]
+    fn exp_ease() {
+        for k in &[0.5, 1.0, 2.0, 3.0] {
+            let (a, b) = (Animation::ExpA(*k), Animation::ExpB(*k));
+            for p in &[0.0, 0.5, 1.0] {
+                assert_near(a.ease(*p), *p);
+                assert_near(b.ease(*p), *p);
+            }
+            // The curves are symmetric about the middle.
+            assert_near(a.ease(0.2) + a.ease(0.8), 1.0);
+            assert_near(b.ease(0.2) + b.ease(0.8), 1.0);
+        }
+        assert_near(Animation::ExpB(2.0).ease(0.25), 0.125);
+        assert_near(Animation::ExpA(2.0).ease(0.25), 0.375);
+        assert_near(Animation::Linear.ease(1.5), 1.0);
+        assert_near(Animation::Jump.ease(0.5), 0.0);
+    }
+
+    #[test]
+    fn sample_interpolates() {
+        let model = model(vec![
+            frame(0.0, 100, Animation::Linear),
+            frame(10.0, 100, Animation::Jump),
+            frame(20.0, 0, Animation::Done),
+        ]);
+        assert_eq!(model.duration(), 200);
+        let state = model.sample(25.0);
+        assert_eq!(state.frame, 0);
+        assert_near(state.progress, 0.25);
+        assert_near(x(&state.pose), 2.5);
+        assert!(state.layers.is_empty() && state.fade.is_none());
+
+        let state = model.sample(150.0);
+        assert_eq!(state.frame, 1);
+        assert_near(x(&state.pose), 10.0);
+
+        // Before the start and after the end, the ends are held.
+        assert_near(x(&model.sample(-50.0).pose), 0.0);
+        let state = model.sample(500.0);
+        assert_eq!(state.frame, 2);
+        assert_near(x(&state.pose), 20.0);
+    }
+
+    #[test]
+    fn sample_mixed_ops_by_matrix() {
+        let mut model = model(vec![
+            frame(0.0, 100, Animation::Linear),
+            frame(0.0, 0, Animation::Done),
+        ]);
+        model.frames[1].transforms[0] = vec![TransformOp::Scale(3.0, 3.0, 1.0)];
+        let m = model.sample(50.0).pose.transforms[0];
+        assert_near(m.a, 2.0);
+        assert_near(m.d, 2.0);
+        assert_near(m.e, 0.0);
+    }
+
+    #[test]
+    fn sample_about_pivot() {
+        let mut model = model(vec![Frame {
+            transforms: vec![vec![TransformOp::Scale(2.0, 2.0, 1.0)]],
+            properties: vec![],
+            morph: Morph::None,
+            delay: 0,
+            animation: Animation::Done,
+        }]);
+        model.groups[0].1.push(GroupProperty::Pivot(5.0, 5.0));
+        let m = model.sample(0.0).pose.transforms[0];
+        assert_eq!(m.apply(5.0, 5.0), (5.0, 5.0));
+        assert_eq!(m.apply(6.0, 5.0), (7.0, 5.0));
+    }
+
+    #[test]
+    fn sample_fade_and_layer() {
+        let model = model(vec![
+            frame(0.0, 100, Animation::Layer),
+            frame(10.0, 100, Animation::Layer),
+            frame(20.0, 100, Animation::Fade),
+            frame(30.0, 0, Animation::Done),
+        ]);
+        let state = model.sample(150.0);
+        assert_eq!(state.frame, 1);
+        assert_eq!(state.layers.len(), 1);
+        assert_near(x(&state.layers[0]), 0.0);
+        assert_near(x(&state.pose), 10.0);
+
+        let state = model.sample(275.0);
+        assert_eq!(state.frame, 2);
+        assert_eq!(state.layers.len(), 2);
+        assert_near(x(&state.pose), 20.0);
+        let (fade, amount) = state.fade.unwrap();
+        assert_near(x(&fade), 30.0);
+        assert_near(amount, 0.75);
+    }
+
+    #[test]
+    fn sample_without_frames() {
+        let state = model(vec![]).sample(100.0);
+        assert_eq!(state.frame, 0);
+        assert_eq!(state.pose.transforms, vec![Affine2::IDENTITY]);
+    }
+
+    #[test]
+    fn cubic_bezier_ease() {
+        let ease = Animation::CubicBezier(0.25, 0.1, 0.25, 1.0);
+        assert_near(ease.ease(0.0), 0.0);
+        assert_near(ease.ease(0.1), 0.094_796);
+        assert_near(ease.ease(0.25), 0.408_511);
+        assert_near(ease.ease(0.5), 0.802_403);
+        assert_near(ease.ease(0.75), 0.960_459);
+        assert_near(ease.ease(0.9), 0.994_316);
+        assert_near(ease.ease(1.0), 1.0);
+    }
+
+    #[test]
+    fn steps_ease() {
+        use StepPosition::*;
+        // Progress at the start, either side of the first and last step
+        // boundaries, and at the end.
+        let progress = [0.0, 0.24, 0.25, 0.74, 0.75, 1.0];
+        for (position, expected) in [
+            (JumpStart, [0.25, 0.25, 0.5, 0.75, 1.0, 1.0]),
+            (JumpEnd, [0.0, 0.0, 0.25, 0.5, 0.75, 1.0]),
+            (JumpNone, [0.0, 0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0, 1.0]),
+            (JumpBoth, [0.2, 0.2, 0.4, 0.6, 0.8, 1.0]),
+        ] {
+            let ease = Animation::Steps(4, position);
+            for (p, expected) in progress.iter().zip(&expected) {
+                assert_near(ease.ease(*p), *expected);
+            }
+        }
+    }
+
+    #[test]
+    fn single_step_jump_none() {
+        let ease = Animation::Steps(1, StepPosition::JumpNone);
+        assert_near(ease.ease(0.0), 0.0);
+        assert_near(ease.ease(0.5), 0.0);
+        assert_near(ease.ease(0.999), 0.0);
+        assert_near(ease.ease(1.0), 1.0);
+    }
+
+    #[test]
+    fn sample_loops() {
+        let mut model = model(vec![
+            frame(0.0, 100, Animation::Linear),
+            frame(10.0, 0, Animation::Done),
+        ]);
+        model.playback.loops = 3;
+        assert_eq!(model.total_duration(), Some(300));
+        assert_near(x(&model.sample(50.0).pose), 5.0);
+        assert_near(x(&model.sample(150.0).pose), 5.0);
+        assert_near(x(&model.sample(275.0).pose), 7.5);
+        // The end of the last loop is held.
+        assert_near(x(&model.sample(300.0).pose), 10.0);
+        assert_near(x(&model.sample(1000.0).pose), 10.0);
+
+        model.playback.loops = 0;
+        assert_eq!(model.total_duration(), None);
+        assert_near(x(&model.sample(1025.0).pose), 2.5);
+    }
+
+    #[test]
+    fn sample_ping_pong() {
+        let mut model = model(vec![
+            frame(0.0, 100, Animation::Linear),
+            frame(10.0, 0, Animation::Done),
+        ]);
+        model.playback.loops = 2;
+        model.playback.ping_pong = true;
+        assert_near(x(&model.sample(25.0).pose), 2.5);
+        assert_near(x(&model.sample(125.0).pose), 7.5);
+        // The second loop plays backwards, ending on the first frame.
+        assert_near(x(&model.sample(200.0).pose), 0.0);
+        assert_near(x(&model.sample(500.0).pose), 0.0);
+    }
+
+    #[test]
+    fn sample_start_delay_and_duration() {
+        let mut model = model(vec![
+            frame(0.0, 100, Animation::Linear),
+            frame(10.0, 100, Animation::Linear),
+            frame(20.0, 0, Animation::Done),
+        ]);
+        model.playback.start_delay = 1000;
+        model.playback.duration = 400;
+        assert_eq!(model.duration(), 400);
+        assert_eq!(model.total_duration(), Some(1400));
+        assert_near(x(&model.sample(500.0).pose), 0.0);
+        assert_near(x(&model.sample(1000.0).pose), 0.0);
+        // Frame delays are scaled to fit the duration.
+        assert_near(x(&model.sample(1100.0).pose), 5.0);
+        assert_near(x(&model.sample(1300.0).pose), 15.0);
+        assert_near(x(&model.sample(1400.0).pose), 20.0);
+    }
+
+    #[test]
+    fn sample_properties() {
+        let mut model = model(vec![
+            frame(0.0, 100, Animation::Linear),
+            frame(0.0, 0, Animation::Done),
+        ]);
+        model.groups[0].1 = vec![
+            GroupProperty::FillColorRgba([255, 0, 0, 255]),
+            GroupProperty::StrokeWidth(2.0),
+            GroupProperty::JoinStyle(crate::JoinStyle::Round),
+        ];
+        model.frames[1].properties = vec![vec![
+            GroupProperty::FillColorRgba([0, 0, 255, 255]),
+            GroupProperty::Opacity(0.0),
+            // Not animatable, so ignored.
+            GroupProperty::Pivot(5.0, 5.0),
+        ]];
+        let style = model.style(0);
+        assert_eq!(style.fill, [255, 0, 0, 255]);
+        assert_eq!(style.stroke_width, 2.0);
+
+        let state = model.sample(50.0);
+        let style = state.pose.styles[0];
+        assert_eq!(style.fill, [128, 0, 128, 255]);
+        assert_eq!(style.stroke, [0, 0, 0, 0]);
+        assert_near(style.stroke_width, 2.0);
+        assert_near(style.opacity, 0.5);
+        assert_eq!(state.pose.transforms[0], Affine2::IDENTITY);
+
+        // Halfway in linear light is brighter.
+        model.playback.color_space = ColorSpace::Linear;
+        let style = model.sample(50.0).pose.styles[0];
+        assert_eq!(style.fill, [188, 0, 188, 255]);
+    }
+
+    #[test]
+    fn lerp_color_premultiplied() {
+        // Fading in from transparent doesn't pass through black.
+        let color =
+            lerp_color([0, 0, 0, 0], [255, 0, 0, 255], 0.5, ColorSpace::Srgb);
+        assert_eq!(color, [255, 0, 0, 128]);
+        let color = lerp_color(
+            [0, 255, 0, 255],
+            [0, 0, 0, 0],
+            0.25,
+            ColorSpace::Linear,
+        );
+        assert_eq!(color, [0, 255, 0, 191]);
+    }
+
+    /// A graphic drawing a line from (0, 0) to (10, 0) with `model`.
+    fn line(model: Model) -> Graphic {
+        Graphic {
+            attributes: vec![],
+            vertex_list: vec![0.0, 0.0, 10.0, 0.0],
+            group: vec![vec![PathOp::Move(0), PathOp::Line(1)]],
+            gradients: vec![],
+            models: vec![model],
+            bitmaps: vec![],
+        }
+    }
+
+    #[test]
+    fn sample_morphs() {
+        let mut model = model(vec![
+            frame(0.0, 100, Animation::Linear),
+            frame(0.0, 100, Animation::Linear),
+            frame(0.0, 0, Animation::Done),
+        ]);
+        model.frames[1].morph = Morph::Sparse(vec![(1, [10.0, 10.0])]);
+        model.frames[2].morph =
+            Morph::Snapshot(vec![[0.0, 20.0], [10.0, 20.0]]);
+        let graphic = line(model);
+
+        // Vertices that no keyframe moves aren't listed.
+        let state = graphic.sample(0, 50.0).unwrap();
+        assert_eq!(state.pose.vertices, vec![(1, [10.0, 5.0])]);
+        let state = graphic.sample(0, 150.0).unwrap();
+        assert_eq!(
+            state.pose.vertices,
+            vec![(0, [0.0, 10.0]), (1, [10.0, 15.0])]
+        );
+        let state = graphic.sample(0, 200.0).unwrap();
+        assert_eq!(
+            state.pose.vertices,
+            vec![(0, [0.0, 20.0]), (1, [10.0, 20.0])]
+        );
+
+        // Without the graphic, vertices stay put.
+        assert!(graphic.models[0].sample(150.0).pose.vertices.is_empty());
+        assert!(graphic.sample(1, 150.0).is_none());
+    }
+
+    #[test]
+    fn sample_motion_path() {
+        let frame = |distance: f32, delay: u16, animation: Animation| Frame {
+            transforms: vec![
+                vec![TransformOp::Translate(0.0, 5.0, 0.0)],
+                vec![TransformOp::MotionPath(0, distance, true)],
+            ],
+            properties: vec![],
+            morph: Morph::None,
+            delay,
+            animation,
+        };
+        let mut model = model(vec![
+            frame(0.0, 100, Animation::Linear),
+            frame(1.0, 0, Animation::Done),
+        ]);
+        model.groups.push((1, vec![]));
+        let mut graphic = line(model);
+        graphic.group.push(vec![]);
+
+        // The route is followed where group 0 draws it.
+        let state = graphic.sample(0, 50.0).unwrap();
+        assert_eq!(state.pose.transforms[1].apply(0.0, 0.0), (5.0, 5.0));
+
+        // A morph turns the route upward, and the group with it.
+        for frame in &mut graphic.models[0].frames {
+            frame.morph = Morph::Sparse(vec![(1, [0.0, 10.0])]);
+        }
+        let state = graphic.sample(0, 50.0).unwrap();
+        let (x, y) = state.pose.transforms[1].apply(1.0, 0.0);
+        assert_near(x, 0.0);
+        assert_near(y, 11.0);
+
+        // Without the graphic, motion paths are left out.
+        let state = graphic.models[0].sample(50.0);
+        assert_eq!(state.pose.transforms[1], Affine2::IDENTITY);
+
+        // Under a parent, the route is mapped into the parent's space.
+        for frame in &mut graphic.models[0].frames {
+            frame.morph = Morph::None;
+        }
+        graphic.models[0].groups[1].1.push(GroupProperty::Parent(0));
+        let state = graphic.sample(0, 50.0).unwrap();
+        assert_eq!(state.pose.transforms[1].apply(0.0, 0.0), (5.0, 5.0));
+    }
+
+    /// A model of three groups, each translated by (1, 0), with parents.
+    fn family(parents: &[Option<u32>]) -> Model {
+        let mut model = model(vec![Frame {
+            transforms: vec![
+                vec![TransformOp::Translate(1.0, 0.0, 0.0)];
+                parents.len()
+            ],
+            properties: vec![],
+            morph: Morph::None,
+            delay: 0,
+            animation: Animation::Done,
+        }]);
+        model.groups = parents
+            .iter()
+            .map(|parent| {
+                (
+                    0,
+                    parent.iter().map(|p| GroupProperty::Parent(*p)).collect(),
+                )
+            })
+            .collect();
+        model
+    }
+
+    #[test]
+    fn sample_hierarchy() {
+        let mut model = family(&[None, Some(0), Some(1)]);
+        let pose = model.sample(0.0).pose;
+        let xs: Vec<f32> = pose.transforms.iter().map(|m| m.e).collect();
+        assert_eq!(xs, vec![1.0, 2.0, 3.0]);
+
+        // A parent's pivot doesn't move its children.
+        model.groups[0].1.push(GroupProperty::Pivot(5.0, 5.0));
+        model.frames[0].transforms[0] = vec![TransformOp::Scale(2.0, 2.0, 1.0)];
+        let pose = model.sample(0.0).pose;
+        assert_eq!(pose.transforms[1].apply(0.0, 0.0), (-3.0, -5.0));
+    }
+
+    #[test]
+    fn sample_parent_cycle() {
+        // Groups 0 and 1 are each other's parent; 2 is a child of 1.
+        let model = family(&[Some(1), Some(0), Some(1)]);
+        let pose = model.sample(0.0).pose;
+        let xs: Vec<f32> = pose.transforms.iter().map(|m| m.e).collect();
+        assert_eq!(xs, vec![1.0, 1.0, 1.0]);
+
+        // A group can't be its own parent.
+        let model = family(&[Some(0)]);
+        assert_eq!(model.sample(0.0).pose.transforms[0].e, 1.0);
+    }
+}
@@ -0,0 +1,266 @@
+use crate::Clip;
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use pix::rgb::SRgba8;
+use pix::Raster;
+use png_pong::FrameEncoder;
+use std::convert::TryInto;
+use std::io::Write;
+
+/// The 8 bytes every PNG file starts with.
+const PNG_SIGNATURE: [u8; 8] =
+    [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/// Get the CRC-32 of a chunk's type and data, as PNG requires.
+fn crc32(kind: &[u8], data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+    let mut crc = !0u32;
+    for byte in kind.iter().chain(data) {
+        crc = table[((crc ^ u32::from(*byte)) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+/// Write one PNG chunk.
+fn write_chunk<W: Write>(
+    writer: &mut W,
+    kind: &[u8],
+    data: &[u8],
+) -> Option<()> {
+    let len: u32 = data.len().try_into().ok()?;
+    writer.write_all(&len.to_be_bytes()).ok()?;
+    writer.write_all(kind).ok()?;
+    writer.write_all(data).ok()?;
+    writer.write_all(&crc32(kind, data).to_be_bytes()).ok()
+}
+
+/// Get the `IHDR` data of an 8-bit RGBA image.
+fn header(width: u32, height: u32) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Depth 8, color type 6 (RGBA), deflate, adaptive filtering, no
+    // interlacing.
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    header
+}
+
+/// Compress the pixels of a raster into image data, with every scanline
+/// filtered by the difference from the pixel to its left (filter type 1).
+fn image_data(raster: &Raster<SRgba8>) -> Vec<u8> {
+    let stride = raster.width() as usize * 4;
+    let mut filtered =
+        Vec::with_capacity((stride + 1) * raster.height() as usize);
+    for row in raster.as_u8_slice().chunks(stride) {
+        filtered.push(1);
+        filtered.extend_from_slice(&row[..4]);
+        for (i, byte) in row.iter().enumerate().skip(4) {
+            filtered.push(byte.wrapping_sub(row[i - 4]));
+        }
+    }
+    compress_to_vec_zlib(&filtered, 6)
+}
+
+/// Get the numerator and denominator of a frame delay in milliseconds, as
+/// an `fcTL` chunk stores it.
+fn delay_fraction(delay: u32) -> (u16, u16) {
+    match delay.try_into() {
+        Ok(delay) => (delay, 1000),
+        Err(_) => ((delay / 10).min(u16::MAX.into()) as u16, 100),
+    }
+}
+
+impl Clip {
+    /// Export the frames as an animated PNG (APNG) file, with every frame
+    /// in 8-bit RGBA.
+    pub fn save_apng<W: Write>(&self, mut writer: W) -> Option<()> {
+        let count: u32 = self.frames.len().try_into().ok()?;
+        let (width, height) = {
+            let (raster, _) = self.frames.first()?;
+            (raster.width(), raster.height())
+        };
+
+        writer.write_all(&PNG_SIGNATURE).ok()?;
+        let mut sequence = 0u32;
+        for (i, (raster, delay)) in self.frames.iter().enumerate() {
+            if (raster.width(), raster.height()) != (width, height) {
+                return None;
+            }
+            let image = image_data(raster);
+            if i == 0 {
+                write_chunk(&mut writer, b"IHDR", &header(width, height))?;
+                let mut actl = Vec::new();
+                actl.extend_from_slice(&count.to_be_bytes());
+                actl.extend_from_slice(&self.loops.to_be_bytes());
+                write_chunk(&mut writer, b"acTL", &actl)?;
+            }
+
+            // Every frame covers the whole canvas, and replaces the last.
+            let (num, den) = delay_fraction(*delay);
+            let mut fctl = Vec::new();
+            fctl.extend_from_slice(&sequence.to_be_bytes());
+            fctl.extend_from_slice(&width.to_be_bytes());
+            fctl.extend_from_slice(&height.to_be_bytes());
+            fctl.extend_from_slice(&[0; 8]);
+            fctl.extend_from_slice(&num.to_be_bytes());
+            fctl.extend_from_slice(&den.to_be_bytes());
+            fctl.extend_from_slice(&[0, 0]);
+            write_chunk(&mut writer, b"fcTL", &fctl)?;
+            sequence += 1;
+
+            if i == 0 {
+                write_chunk(&mut writer, b"IDAT", &image)?;
+            } else {
+                let mut fdat = sequence.to_be_bytes().to_vec();
+                fdat.extend_from_slice(&image);
+                write_chunk(&mut writer, b"fdAT", &fdat)?;
+                sequence += 1;
+            }
+        }
+        write_chunk(&mut writer, b"IEND", &[])
+    }
+
+    /// Export each sample as a PNG file, calling `writer` to get the
+    /// destination for each sample index, for formats with a constant frame
+    /// rate.
+    pub fn save_png_sequence<W, F>(&self, mut writer: F) -> Option<()>
+    where
+        W: Write,
+        F: FnMut(usize) -> Option<W>,
+    {
+        for (i, raster) in self.samples().enumerate() {
+            FrameEncoder::<_, SRgba8>::new(writer(i)?)
+                .still(raster)
+                .ok()?;
+        }
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use png_pong::FrameDecoder;
+
+    /// Split an APNG file into its chunks.
+    fn chunks(apng: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        assert_eq!(apng[..8], PNG_SIGNATURE);
+        let mut chunks = Vec::new();
+        let mut rest = &apng[8..];
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[0..4].try_into().unwrap());
+            let len = len as usize;
+            let kind: [u8; 4] = rest[4..8].try_into().unwrap();
+            let data = rest[8..8 + len].to_vec();
+            let crc =
+                u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+            assert_eq!(crc, crc32(&kind, &data));
+            chunks.push((kind, data));
+            rest = &rest[12 + len..];
+        }
+        chunks
+    }
+
+    /// Decode the image data of one frame, as a PNG with the APNG's header.
+    fn decode(header: &[u8], image: &[u8]) -> Raster<SRgba8> {
+        let mut png = PNG_SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", header).unwrap();
+        write_chunk(&mut png, b"IDAT", image).unwrap();
+        write_chunk(&mut png, b"IEND", &[]).unwrap();
+        let frame = FrameDecoder::<_, SRgba8>::new(png.as_slice())
+            .next()
+            .unwrap()
+            .unwrap();
+        frame.raster
+    }
+
+    #[test]
+    fn apng_round_trip() {
+        // Opaque, gray and translucent frames are all saved as RGBA.
+        let frames: Vec<Raster<SRgba8>> =
+            [[255, 0, 0, 255], [128, 128, 128, 255], [0, 64, 255, 100]]
+                .iter()
+                .map(|color| {
+                    let mut pixels = color.repeat(6);
+                    pixels[..4].copy_from_slice(&[0, 0, 0, 0]);
+                    Raster::with_u8_buffer(3, 2, pixels)
+                })
+                .collect();
+        let clip = Clip {
+            frames: frames.iter().cloned().zip(vec![40, 70_000, 10]).collect(),
+            loops: 2,
+            fps: 10.0,
+        };
+        let mut apng = Vec::new();
+        clip.save_apng(&mut apng).unwrap();
+
+        let chunks = chunks(&apng);
+        let kinds: Vec<&[u8]> = chunks.iter().map(|(k, _)| &k[..]).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &b"IHDR"[..],
+                b"acTL",
+                b"fcTL",
+                b"IDAT",
+                b"fcTL",
+                b"fdAT",
+                b"fcTL",
+                b"fdAT",
+                b"IEND"
+            ]
+        );
+        let header = &chunks[0].1;
+        assert_eq!(header[..], [0, 0, 0, 3, 0, 0, 0, 2, 8, 6, 0, 0, 0]);
+        assert_eq!(chunks[1].1, [0, 0, 0, 3, 0, 0, 0, 2]);
+
+        // Delays too long for milliseconds are stored in centiseconds.
+        let delay = |fctl: &[u8]| (fctl[20..24]).to_vec();
+        assert_eq!(delay(&chunks[2].1), [0, 40, 3, 232]);
+        assert_eq!(delay(&chunks[4].1), [27, 88, 0, 100]);
+
+        // Sequence numbers count up through `fcTL` and `fdAT` chunks.
+        let sequence =
+            |data: &[u8]| u32::from_be_bytes(data[..4].try_into().unwrap());
+        let numbers: Vec<u32> = [2, 4, 5, 6, 7]
+            .iter()
+            .map(|i| sequence(&chunks[*i].1))
+            .collect();
+        assert_eq!(numbers, vec![0, 1, 2, 3, 4]);
+
+        let images = [&chunks[3].1[..], &chunks[5].1[4..], &chunks[7].1[4..]];
+        for (frame, image) in frames.iter().zip(images.iter()) {
+            let raster = decode(header, image);
+            assert_eq!(raster.as_u8_slice(), frame.as_u8_slice());
+        }
+    }
+
+    #[test]
+    fn apng_mismatched_frames() {
+        let clip = Clip {
+            frames: vec![
+                (Raster::with_clear(2, 2), 10),
+                (Raster::with_clear(2, 3), 10),
+            ],
+            loops: 0,
+            fps: 10.0,
+        };
+        assert!(clip.save_apng(&mut Vec::new()).is_none());
+        let clip = Clip {
+            frames: vec![],
+            loops: 0,
+            fps: 10.0,
+        };
+        assert!(clip.save_apng(&mut Vec::new()).is_none());
+    }
+}
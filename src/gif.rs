@@ -0,0 +1,535 @@
+use crate::Clip;
+use pix::{rgb::SRgba8, Raster};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::io::Write;
+
+/// Pixels with less alpha than this are transparent, as GIF has no partial
+/// transparency.
+const ALPHA_THRESHOLD: u8 = 128;
+
+/// Largest LZW code, as GIF limits codes to 12 bits.
+const MAX_CODE: u16 = 4095;
+
+/// Settings for GIF export.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GifSettings {
+    /// Share one palette between every frame, rather than choosing one for
+    /// each frame
+    pub global_palette: bool,
+    /// Spread the difference between each pixel and its palette color over
+    /// its neighbours (Floyd-Steinberg dithering)
+    pub dither: bool,
+}
+
+/// Colors used by one or more frames, counted by pixel.
+#[derive(Default)]
+struct Histogram {
+    /// Sums of the red, green and blue channels, and the number of pixels,
+    /// of each color (reduced to 5 bits a channel)
+    colors: HashMap<u16, [u64; 4]>,
+    /// Whether any pixel is transparent
+    transparent: bool,
+}
+
+impl Histogram {
+    /// Count the colors of a frame.
+    fn add(&mut self, raster: &Raster<SRgba8>) {
+        for pixel in raster.as_u8_slice().chunks_exact(4) {
+            if pixel[3] < ALPHA_THRESHOLD {
+                self.transparent = true;
+                continue;
+            }
+            let key = (u16::from(pixel[0]) >> 3) << 10
+                | (u16::from(pixel[1]) >> 3) << 5
+                | u16::from(pixel[2]) >> 3;
+            let sums = self.colors.entry(key).or_insert([0; 4]);
+            for (sum, channel) in sums.iter_mut().zip(&pixel[..3]) {
+                *sum += u64::from(*channel);
+            }
+            sums[3] += 1;
+        }
+    }
+
+    /// Choose a palette by median cut.  When there are transparent pixels,
+    /// the last entry is left for them.
+    fn palette(&self) -> Palette {
+        let size = if self.transparent { 255 } else { 256 };
+        let entries: Vec<([u8; 3], u64)> = self
+            .colors
+            .values()
+            .map(|[r, g, b, count]| {
+                let mean = |sum: u64| (sum / count) as u8;
+                ([mean(*r), mean(*g), mean(*b)], *count)
+            })
+            .collect();
+
+        let mut boxes = vec![entries];
+        while boxes.len() < size {
+            // Split the box with the widest range of one channel.
+            let widest = boxes
+                .iter()
+                .enumerate()
+                .filter(|(_, entries)| entries.len() > 1)
+                .map(|(i, entries)| {
+                    let (channel, range) = widest_channel(entries);
+                    (i, channel, range)
+                })
+                .max_by_key(|(_, _, range)| *range);
+            let (i, channel, _) = match widest {
+                Some(widest) => widest,
+                None => break,
+            };
+            let mut entries = boxes.swap_remove(i);
+            entries.sort_by_key(|(color, _)| color[channel]);
+            let half = entries.iter().map(|(_, count)| count).sum::<u64>() / 2;
+            let mut seen = 0;
+            let mut split = entries.len() - 1;
+            for (j, (_, count)) in entries.iter().enumerate() {
+                seen += count;
+                if seen > half {
+                    split = j.clamp(1, entries.len() - 1);
+                    break;
+                }
+            }
+            let rest = entries.split_off(split);
+            boxes.push(entries);
+            boxes.push(rest);
+        }
+
+        let colors = boxes
+            .iter()
+            .filter(|entries| !entries.is_empty())
+            .map(|entries| {
+                let mut sums = [0u64; 3];
+                let mut total = 0u64;
+                for (color, count) in entries {
+                    for (sum, channel) in sums.iter_mut().zip(color) {
+                        *sum += u64::from(*channel) * count;
+                    }
+                    total += count;
+                }
+                let mean = |sum: u64| (sum / total.max(1)) as u8;
+                [mean(sums[0]), mean(sums[1]), mean(sums[2])]
+            })
+            .collect::<Vec<_>>();
+        let transparent = if self.transparent {
+            Some(colors.len() as u8)
+        } else {
+            None
+        };
+        Palette {
+            colors,
+            transparent,
+            nearest: HashMap::new(),
+        }
+    }
+}
+
+/// Get the channel with the widest range of values in a box, and the range.
+fn widest_channel(entries: &[([u8; 3], u64)]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let values = entries.iter().map(|(color, _)| color[channel]);
+            let min = values.clone().min().unwrap_or(0);
+            let max = values.max().unwrap_or(0);
+            (channel, max - min)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap_or((0, 0))
+}
+
+/// The colors a frame is drawn with.
+struct Palette {
+    /// Opaque colors
+    colors: Vec<[u8; 3]>,
+    /// Index of the transparent color, after the opaque ones
+    transparent: Option<u8>,
+    /// Index of the nearest color, for each color looked up so far
+    nearest: HashMap<[u8; 3], u8>,
+}
+
+impl Palette {
+    /// Get the index of the palette color nearest to a color.
+    fn index(&mut self, color: [u8; 3]) -> u8 {
+        let colors = &self.colors;
+        *self.nearest.entry(color).or_insert_with(|| {
+            let distance = |other: &[u8; 3]| -> i32 {
+                color
+                    .iter()
+                    .zip(other)
+                    .map(|(a, b)| (i32::from(*a) - i32::from(*b)).pow(2))
+                    .sum()
+            };
+            (0..colors.len())
+                .min_by_key(|i| distance(&colors[*i]))
+                .unwrap_or(0) as u8
+        })
+    }
+
+    /// Get the number of bits in an index, for a color table that is a
+    /// power of 2 long.
+    fn bits(&self) -> u8 {
+        let len = self.colors.len() + self.transparent.is_some() as usize;
+        let mut bits = 1;
+        while 1 << bits < len {
+            bits += 1;
+        }
+        bits
+    }
+
+    /// Write the color table, padded to its power of 2 length.
+    fn write<W: Write>(&self, writer: &mut W) -> Option<()> {
+        let len = 1 << self.bits();
+        for i in 0..len {
+            let color = self.colors.get(i).unwrap_or(&[0, 0, 0]);
+            writer.write_all(color).ok()?;
+        }
+        Some(())
+    }
+
+    /// Map each pixel of a frame to a palette index.
+    fn indices(&mut self, raster: &Raster<SRgba8>, dither: bool) -> Vec<u8> {
+        let width = raster.width() as usize;
+        let pixels = raster.as_u8_slice();
+        let mut indices = Vec::with_capacity(pixels.len() / 4);
+        // Error carried to this row and the next, for each channel.
+        let mut error = vec![[0.0f32; 3]; width * 2 + 2];
+        for (i, pixel) in pixels.chunks_exact(4).enumerate() {
+            let x = i % width;
+            if x == 0 && dither {
+                let (this, next) = error.split_at_mut(width + 1);
+                this.copy_from_slice(&next[..width + 1]);
+                for e in next.iter_mut() {
+                    *e = [0.0; 3];
+                }
+            }
+            if pixel[3] < ALPHA_THRESHOLD {
+                indices.push(self.transparent.unwrap_or(0));
+                continue;
+            }
+            if !dither {
+                indices.push(self.index([pixel[0], pixel[1], pixel[2]]));
+                continue;
+            }
+            let mut wanted = [0.0f32; 3];
+            let mut color = [0u8; 3];
+            for c in 0..3 {
+                wanted[c] = f32::from(pixel[c]) + error[x][c];
+                color[c] = wanted[c].round().clamp(0.0, 255.0) as u8;
+            }
+            let index = self.index(color);
+            let got = self.colors[usize::from(index)];
+            for c in 0..3 {
+                let e = wanted[c] - f32::from(got[c]);
+                error[x + 1][c] += e * 7.0 / 16.0;
+                if x > 0 {
+                    error[width + x][c] += e * 3.0 / 16.0;
+                }
+                error[width + 1 + x][c] += e * 5.0 / 16.0;
+                error[width + 2 + x][c] += e * 1.0 / 16.0;
+            }
+            indices.push(index);
+        }
+        indices
+    }
+}
+
+/// Packs variable-width codes into bytes, least significant bit first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+    count: u8,
+}
+
+impl BitWriter {
+    fn push(&mut self, code: u16, width: u8) {
+        self.bits |= u32::from(code) << self.count;
+        self.count += width;
+        while self.count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Compress palette indices with GIF's variant of LZW.
+fn lzw(indices: &[u8], min_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_size;
+    let end = clear + 1;
+    let mut writer = BitWriter {
+        bytes: Vec::new(),
+        bits: 0,
+        count: 0,
+    };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut width = min_size + 1;
+    writer.push(clear, width);
+
+    let mut prefix = match indices.first() {
+        Some(&index) => u16::from(index),
+        None => {
+            writer.push(end, width);
+            return writer.finish();
+        }
+    };
+    for &index in &indices[1..] {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+        writer.push(prefix, width);
+        if next > MAX_CODE {
+            writer.push(clear, width);
+            table.clear();
+            next = end + 1;
+            width = min_size + 1;
+        } else {
+            table.insert((prefix, index), next);
+            if next == 1 << width && width < 12 {
+                width += 1;
+            }
+            next += 1;
+        }
+        prefix = u16::from(index);
+    }
+    writer.push(prefix, width);
+    writer.push(end, width);
+    writer.finish()
+}
+
+/// Write data as sub-blocks of up to 255 bytes, and the block terminator.
+fn write_blocks<W: Write>(writer: &mut W, data: &[u8]) -> Option<()> {
+    for block in data.chunks(255) {
+        writer.write_all(&[block.len() as u8]).ok()?;
+        writer.write_all(block).ok()?;
+    }
+    writer.write_all(&[0]).ok()
+}
+
+impl Clip {
+    /// Export the frames as an animated GIF (GIF89a) file.  Pixels less than
+    /// half opaque are transparent.
+    pub fn save_gif<W: Write>(
+        &self,
+        mut writer: W,
+        settings: GifSettings,
+    ) -> Option<()> {
+        let (width, height) = {
+            let (raster, _) = self.frames.first()?;
+            let width: u16 = raster.width().try_into().ok()?;
+            let height: u16 = raster.height().try_into().ok()?;
+            (width, height)
+        };
+        let mut global = if settings.global_palette {
+            let mut histogram = Histogram::default();
+            for (raster, _) in &self.frames {
+                histogram.add(raster);
+            }
+            Some(histogram.palette())
+        } else {
+            None
+        };
+
+        // HEADER & LOGICAL SCREEN DESCRIPTOR
+        writer.write_all(b"GIF89a").ok()?;
+        writer.write_all(&width.to_le_bytes()).ok()?;
+        writer.write_all(&height.to_le_bytes()).ok()?;
+        match &global {
+            Some(palette) => {
+                let packed = 0xF0 | (palette.bits() - 1);
+                writer.write_all(&[packed, 0, 0]).ok()?;
+                palette.write(&mut writer)?;
+            }
+            None => writer.write_all(&[0, 0, 0]).ok()?,
+        }
+
+        // NETSCAPE2.0 loop count, as repeats after the first play.
+        if self.loops != 1 {
+            let repeats = self.loops.saturating_sub(1).min(u16::MAX.into());
+            writer.write_all(&[0x21, 0xFF, 11]).ok()?;
+            writer.write_all(b"NETSCAPE2.0").ok()?;
+            writer.write_all(&[3, 1]).ok()?;
+            writer.write_all(&(repeats as u16).to_le_bytes()).ok()?;
+            writer.write_all(&[0]).ok()?;
+        }
+
+        // Delays are in hundredths of a second, rounded so they add up.
+        let mut shown_ms = 0u64;
+        let mut shown_cs = 0u64;
+        for (raster, delay) in &self.frames {
+            shown_ms += u64::from(*delay);
+            let delay = ((shown_ms + 5) / 10 - shown_cs).min(u16::MAX.into());
+            shown_cs += delay;
+
+            let mut local = if global.is_none() {
+                let mut histogram = Histogram::default();
+                histogram.add(raster);
+                Some(histogram.palette())
+            } else {
+                None
+            };
+            let is_local = local.is_some();
+            let palette = local.as_mut().or(global.as_mut())?;
+
+            // GRAPHIC CONTROL EXTENSION: clear to the background after each
+            // frame, so transparent pixels don't show the last one.
+            let packed = 2 << 2 | palette.transparent.is_some() as u8;
+            writer.write_all(&[0x21, 0xF9, 4, packed]).ok()?;
+            writer.write_all(&(delay as u16).to_le_bytes()).ok()?;
+            writer
+                .write_all(&[palette.transparent.unwrap_or(0), 0])
+                .ok()?;
+
+            // IMAGE DESCRIPTOR
+            writer.write_all(&[0x2C, 0, 0, 0, 0]).ok()?;
+            writer.write_all(&width.to_le_bytes()).ok()?;
+            writer.write_all(&height.to_le_bytes()).ok()?;
+            if is_local {
+                writer.write_all(&[0x80 | (palette.bits() - 1)]).ok()?;
+                palette.write(&mut writer)?;
+            } else {
+                writer.write_all(&[0]).ok()?;
+            }
+
+            // IMAGE DATA
+            let indices = palette.indices(raster, settings.dither);
+            let min_size = palette.bits().max(2);
+            writer.write_all(&[min_size]).ok()?;
+            write_blocks(&mut writer, &lzw(&indices, min_size))?;
+        }
+        writer.write_all(&[0x3B]).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decompress GIF LZW data into palette indices.
+    fn unlzw(data: &[u8], min_size: u8) -> Vec<u8> {
+        let clear = 1u16 << min_size;
+        let end = clear + 1;
+        let reset = |table: &mut Vec<Vec<u8>>| {
+            table.clear();
+            table.extend((0..clear).map(|index| vec![index as u8]));
+            table.extend([Vec::new(), Vec::new()]);
+        };
+        let mut table = Vec::new();
+        reset(&mut table);
+        let mut width = min_size + 1;
+        let (mut bits, mut count) = (0u32, 0u8);
+        let mut bytes = data.iter();
+        let mut prev: Option<Vec<u8>> = None;
+        let mut indices = Vec::new();
+        loop {
+            while count < width {
+                bits |= u32::from(*bytes.next().expect("no end code")) << count;
+                count += 8;
+            }
+            let code = (bits & ((1 << width) - 1)) as u16;
+            bits >>= width;
+            count -= width;
+            if code == clear {
+                reset(&mut table);
+                width = min_size + 1;
+                prev = None;
+                continue;
+            }
+            if code == end {
+                return indices;
+            }
+            let entry = match (table.get(usize::from(code)), &prev) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(prev)) => {
+                    let mut entry = prev.clone();
+                    entry.push(prev[0]);
+                    entry
+                }
+                (None, None) => panic!("unknown code {}", code),
+            };
+            if let Some(mut prev) = prev {
+                if table.len() <= usize::from(MAX_CODE) {
+                    prev.push(entry[0]);
+                    table.push(prev);
+                    if table.len() == 1 << width && width < 12 {
+                        width += 1;
+                    }
+                }
+            }
+            indices.extend(&entry);
+            prev = Some(entry);
+        }
+    }
+
+    /// Read sub-blocks, up to the block terminator.
+    fn read_blocks(data: &[u8], at: &mut usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let len = usize::from(data[*at]);
+            *at += 1;
+            if len == 0 {
+                return bytes;
+            }
+            bytes.extend(&data[*at..*at + len]);
+            *at += len;
+        }
+    }
+
+    #[test]
+    fn lzw_round_trip_past_code_limit() {
+        // Pseudo-random indices fill the code table several times over.
+        let mut seed = 1u32;
+        let indices: Vec<u8> = (0..60_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (seed >> 16) as u8
+            })
+            .collect();
+        assert_eq!(unlzw(&lzw(&indices, 8), 8), indices);
+        let small: Vec<u8> = indices.iter().map(|index| index % 4).collect();
+        assert_eq!(unlzw(&lzw(&small, 2), 2), small);
+    }
+
+    #[test]
+    fn transparent_frame() {
+        let (width, height) = (7, 5);
+        let clip = Clip {
+            frames: vec![(Raster::with_clear(width, height), 100)],
+            loops: 1,
+            fps: 10.0,
+        };
+        let mut gif = Vec::new();
+        clip.save_gif(&mut gif, GifSettings::default()).unwrap();
+
+        // Skip the header and logical screen descriptor, without a global
+        // color table.
+        assert_eq!(gif[10] & 0x80, 0);
+        let mut at = 13;
+        assert_eq!(&gif[at..at + 3], &[0x21, 0xF9, 4]);
+        let packed = gif[at + 3];
+        let transparent = gif[at + 6];
+        assert_eq!(packed & 1, 1);
+        at += 8;
+        assert_eq!(gif[at], 0x2C);
+        let local = gif[at + 9];
+        at += 10 + 3 * (2 << (local & 7));
+        let min_size = gif[at];
+        at += 1;
+        let data = read_blocks(&gif, &mut at);
+        assert_eq!(gif[at], 0x3B);
+
+        let indices = unlzw(&data, min_size);
+        assert_eq!(indices.len(), (width * height) as usize);
+        assert!(indices.iter().all(|index| *index == transparent));
+    }
+}
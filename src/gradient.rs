@@ -0,0 +1,210 @@
+use crate::{Gradient, GradientShape, Spread};
+use std::f32::consts::PI;
+
+impl Spread {
+    /// Map an offset along a gradient into the range 0 to 1.
+    pub fn apply(self, offset: f32) -> f32 {
+        match self {
+            Spread::Pad => offset.clamp(0.0, 1.0),
+            Spread::Reflect => 1.0 - ((offset.rem_euclid(2.0)) - 1.0).abs(),
+            Spread::Repeat => offset.rem_euclid(1.0),
+        }
+    }
+}
+
+impl Gradient {
+    /// Get the offset (0 to 1) of the color at a point in the gradient's
+    /// space, after spreading.  Like SVG, a gradient with zero length or
+    /// radius is the color of its last stop everywhere.
+    pub fn offset_at(&self, [x, y]: [f32; 2]) -> f32 {
+        let offset = match self.shape {
+            GradientShape::Linear { start, end } => {
+                let (dx, dy) = (end[0] - start[0], end[1] - start[1]);
+                let len2 = dx * dx + dy * dy;
+                if len2 == 0.0 {
+                    return 1.0;
+                }
+                ((x - start[0]) * dx + (y - start[1]) * dy) / len2
+            }
+            GradientShape::Radial {
+                center,
+                radius,
+                focus,
+            } => {
+                if radius.is_nan() || radius <= 0.0 {
+                    return 1.0;
+                }
+                // Keep the focus just inside the circle, like browsers.
+                let (mut ex, mut ey) =
+                    (center[0] - focus[0], center[1] - focus[1]);
+                let distance = ex.hypot(ey);
+                let inside = radius * 0.99;
+                if distance > inside {
+                    ex *= inside / distance;
+                    ey *= inside / distance;
+                }
+                let (fx, fy) = (center[0] - ex, center[1] - ey);
+                // Solve for the circle, growing from the focus to the edge,
+                // that the point is on.
+                let (dx, dy) = (x - fx, y - fy);
+                let a = ex * ex + ey * ey - radius * radius;
+                let b = dx * ex + dy * ey;
+                let c = dx * dx + dy * dy;
+                (b - (b * b - a * c).max(0.0).sqrt()) / a
+            }
+            GradientShape::Conic { center, angle } => {
+                let turn = (y - center[1]).atan2(x - center[0]) - angle;
+                return turn.rem_euclid(2.0 * PI) / (2.0 * PI);
+            }
+        };
+        self.spread.apply(offset)
+    }
+
+    /// Get the sRGBA color at an offset (0 to 1), blending the stops on
+    /// either side with premultiplied alpha.
+    pub fn color(&self, offset: f32) -> [u8; 4] {
+        let after = self.stops.iter().position(|(at, _)| *at > offset);
+        let (a, b) = match after {
+            None => match self.stops.last() {
+                Some((_, color)) => return *color,
+                None => return [0, 0, 0, 0],
+            },
+            Some(0) => return self.stops[0].1,
+            Some(i) => (self.stops[i - 1], self.stops[i]),
+        };
+        let t = (offset - a.0) / (b.0 - a.0);
+        let (alpha_a, alpha_b) = (f32::from(a.1[3]), f32::from(b.1[3]));
+        let alpha = alpha_a + (alpha_b - alpha_a) * t;
+        let mut color = [0, 0, 0, alpha.round() as u8];
+        if alpha > 0.0 {
+            for (c, channel) in color.iter_mut().take(3).enumerate() {
+                let from = f32::from(a.1[c]) * alpha_a;
+                let to = f32::from(b.1[c]) * alpha_b;
+                *channel = ((from + (to - from) * t) / alpha).round() as u8;
+            }
+        }
+        color
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Affine2;
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} is not {}", a, b);
+    }
+
+    fn gradient(shape: GradientShape, spread: Spread) -> Gradient {
+        Gradient {
+            shape,
+            stops: vec![(0.0, [255, 0, 0, 255]), (1.0, [0, 0, 255, 255])],
+            spread,
+            transform: Affine2::IDENTITY,
+        }
+    }
+
+    #[test]
+    fn spread() {
+        for (offset, pad, reflect, repeat) in &[
+            (0.25, 0.25, 0.25, 0.25),
+            (1.25, 1.0, 0.75, 0.25),
+            (2.25, 1.0, 0.25, 0.25),
+            (-0.25, 0.0, 0.25, 0.75),
+            (-1.25, 0.0, 0.75, 0.75),
+        ] {
+            assert_near(Spread::Pad.apply(*offset), *pad);
+            assert_near(Spread::Reflect.apply(*offset), *reflect);
+            assert_near(Spread::Repeat.apply(*offset), *repeat);
+        }
+    }
+
+    #[test]
+    fn linear_offsets() {
+        let shape = GradientShape::Linear {
+            start: [2.0, 0.0],
+            end: [2.0, 4.0],
+        };
+        let pad = gradient(shape, Spread::Pad);
+        assert_near(pad.offset_at([7.0, 1.0]), 0.25);
+        assert_near(pad.offset_at([0.0, 6.0]), 1.0);
+        assert_near(gradient(shape, Spread::Repeat).offset_at([0.0, 6.0]), 0.5);
+
+        let empty = GradientShape::Linear {
+            start: [1.0, 1.0],
+            end: [1.0, 1.0],
+        };
+        assert_eq!(gradient(empty, Spread::Pad).offset_at([0.0, 0.0]), 1.0);
+    }
+
+    #[test]
+    fn radial_offsets() {
+        let centered = GradientShape::Radial {
+            center: [0.0, 0.0],
+            radius: 4.0,
+            focus: [0.0, 0.0],
+        };
+        let g = gradient(centered, Spread::Pad);
+        assert_near(g.offset_at([0.0, 0.0]), 0.0);
+        assert_near(g.offset_at([3.0, 0.0]), 0.75);
+        assert_near(g.offset_at([0.0, -2.0]), 0.5);
+        assert_near(g.offset_at([8.0, 0.0]), 1.0);
+
+        // Offsets grow from the focus to the circle on each side.
+        let focused = GradientShape::Radial {
+            center: [0.0, 0.0],
+            radius: 4.0,
+            focus: [2.0, 0.0],
+        };
+        let g = gradient(focused, Spread::Pad);
+        assert_near(g.offset_at([2.0, 0.0]), 0.0);
+        assert_near(g.offset_at([3.0, 0.0]), 0.5);
+        assert_near(g.offset_at([-1.0, 0.0]), 0.5);
+
+        let empty = GradientShape::Radial {
+            center: [0.0, 0.0],
+            radius: 0.0,
+            focus: [0.0, 0.0],
+        };
+        assert_eq!(gradient(empty, Spread::Pad).offset_at([1.0, 1.0]), 1.0);
+    }
+
+    #[test]
+    fn conic_offsets() {
+        let shape = GradientShape::Conic {
+            center: [1.0, 1.0],
+            angle: PI / 2.0,
+        };
+        let g = gradient(shape, Spread::Pad);
+        assert_near(g.offset_at([1.0, 3.0]), 0.0);
+        assert_near(g.offset_at([-1.0, 1.0]), 0.25);
+        assert_near(g.offset_at([1.0, -1.0]), 0.5);
+        assert_near(g.offset_at([3.0, 1.0]), 0.75);
+    }
+
+    #[test]
+    fn stop_colors() {
+        let mut g = gradient(
+            GradientShape::Conic {
+                center: [0.0, 0.0],
+                angle: 0.0,
+            },
+            Spread::Pad,
+        );
+        g.stops = vec![
+            (0.25, [255, 0, 0, 255]),
+            (0.75, [0, 0, 255, 0]),
+            (0.75, [0, 255, 0, 255]),
+        ];
+        assert_eq!(g.color(0.0), [255, 0, 0, 255]);
+        // Colors are blended premultiplied, so red doesn't turn blue as it
+        // fades out.
+        assert_eq!(g.color(0.5), [255, 0, 0, 128]);
+        // Stops at the same offset are a hard edge.
+        assert_eq!(g.color(0.75), [0, 255, 0, 255]);
+        assert_eq!(g.color(1.0), [0, 255, 0, 255]);
+        g.stops.clear();
+        assert_eq!(g.color(0.5), [0, 0, 0, 0]);
+    }
+}
@@ -1,9 +1,42 @@
 #[cfg(feature = "render")]
 mod render;
 
+#[cfg(feature = "render")]
+mod gif;
+
+#[cfg(feature = "render")]
+mod shade;
+
+#[cfg(feature = "render")]
+mod stroke;
+
+#[cfg(feature = "render")]
+mod y4m;
+
+#[cfg(feature = "png")]
+mod png;
+
+#[cfg(all(feature = "render", feature = "png"))]
+mod apng;
+
+mod affine;
+mod anim;
+mod gradient;
+mod path;
+mod resource;
 mod rvg;
+mod texture;
+mod timeline;
 
+pub use crate::affine::*;
+pub use crate::anim::*;
+pub use crate::path::*;
+pub use crate::resource::*;
 pub use crate::rvg::*;
 
+#[cfg(feature = "render")]
+pub use crate::gif::*;
 #[cfg(feature = "render")]
 pub use crate::render::*;
+#[cfg(feature = "render")]
+pub use crate::y4m::*;
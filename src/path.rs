@@ -0,0 +1,450 @@
+use crate::{Graphic, PathOp};
+
+/// Fewest line segments each curve is flattened into.
+const CURVE_SEGMENTS: usize = 16;
+
+/// Most line segments each curve is flattened into.
+const MAX_CURVE_SEGMENTS: usize = 1024;
+
+/// A subpath flattened into line segments.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contour {
+    /// Points of the line segments.  Closed contours end with their first
+    /// point.
+    pub points: Vec<[f32; 2]>,
+    /// Whether the subpath was closed
+    pub closed: bool,
+}
+
+impl Contour {
+    /// Get the length of the contour.
+    pub fn length(&self) -> f32 {
+        self.points
+            .windows(2)
+            .map(|pair| distance(pair[0], pair[1]))
+            .sum()
+    }
+
+    /// Get the point at a distance along the contour, and the unit tangent
+    /// there.  Distances are clamped to the contour.
+    pub fn point_at(&self, along: f32) -> Option<([f32; 2], [f32; 2])> {
+        let first = *self.points.first()?;
+        let mut tangent = [1.0, 0.0];
+        let mut left = along.max(0.0);
+        for pair in self.points.windows(2) {
+            let length = distance(pair[0], pair[1]);
+            if length == 0.0 {
+                continue;
+            }
+            let [ax, ay] = pair[0];
+            let [bx, by] = pair[1];
+            tangent = [(bx - ax) / length, (by - ay) / length];
+            if left <= length {
+                let t = left / length;
+                return Some((
+                    [ax + (bx - ax) * t, ay + (by - ay) * t],
+                    tangent,
+                ));
+            }
+            left -= length;
+        }
+        Some((*self.points.last().unwrap_or(&first), tangent))
+    }
+
+    /// Split the contour into open dashes, alternating between `pattern`
+    /// lengths that are drawn and skipped, starting `offset` into the
+    /// pattern.  As in SVG, a pattern with an odd number of lengths is
+    /// repeated, and one with negative lengths or summing to zero doesn't
+    /// split the contour.  A dash crossing the start of a closed contour
+    /// stays in one piece.
+    pub fn dash(&self, pattern: &[f32], offset: f32) -> Vec<Contour> {
+        dash_with(&self.points, self.closed, pattern, offset)
+            .into_iter()
+            .map(|(points, closed)| Contour { points, closed })
+            .collect()
+    }
+}
+
+/// Split flattened points into dashes, as `Contour::dash()` does, with their
+/// other values interpolated along with their positions.
+pub(crate) fn dash_with<const N: usize>(
+    points: &[[f32; N]],
+    closed: bool,
+    pattern: &[f32],
+    offset: f32,
+) -> Vec<(Vec<[f32; N]>, bool)> {
+    let total: f32 = pattern.iter().sum();
+    let valid = total > 0.0 && pattern.iter().all(|length| *length >= 0.0);
+    if !valid {
+        return vec![(points.to_vec(), closed)];
+    }
+    let pattern: Vec<f32> = if pattern.len() % 2 == 1 {
+        pattern.iter().chain(pattern).cloned().collect()
+    } else {
+        pattern.to_vec()
+    };
+    let total: f32 = pattern.iter().sum();
+    let first = match points.first() {
+        Some(first) => *first,
+        None => return Vec::new(),
+    };
+
+    // Find where in the pattern the contour starts.
+    let mut index = 0;
+    let mut phase = offset.rem_euclid(total);
+    while phase >= pattern[index] {
+        phase -= pattern[index];
+        index = (index + 1) % pattern.len();
+    }
+    let mut left = pattern[index] - phase;
+    let starts_on = index % 2 == 0;
+
+    let mut dashes = Vec::new();
+    let mut dash = if starts_on { vec![first] } else { Vec::new() };
+    for pair in points.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let length = distance([a[0], a[1]], [b[0], b[1]]);
+        let mut along = 0.0;
+        while length - along > left {
+            along += left;
+            let t = along / length;
+            let mut point = a;
+            for (value, end) in point.iter_mut().zip(&b) {
+                *value += (end - *value) * t;
+            }
+            if index % 2 == 0 {
+                dash.push(point);
+                dashes.push((std::mem::take(&mut dash), false));
+            } else {
+                dash = vec![point];
+            }
+            index = (index + 1) % pattern.len();
+            left = pattern[index];
+        }
+        left -= length - along;
+        if index % 2 == 0 {
+            dash.push(b);
+        }
+    }
+    if index % 2 == 0 {
+        if dashes.is_empty() && closed && starts_on {
+            return vec![(points.to_vec(), closed)];
+        }
+        if closed && starts_on {
+            // Join the last dash to the first, across the start.
+            let mut joined = dash;
+            joined.extend(dashes[0].0.iter().skip(1));
+            dashes[0].0 = joined;
+        } else if dash.len() > 1 {
+            dashes.push((dash, false));
+        }
+    }
+    dashes
+}
+
+/// Get the distance between two points.
+fn distance([ax, ay]: [f32; 2], [bx, by]: [f32; 2]) -> f32 {
+    (bx - ax).hypot(by - ay)
+}
+
+/// Get the number of line segments to flatten a curve into, from the
+/// positions of its control points: about one for every 4 units along them.
+fn curve_segments<const N: usize>(points: &[[f32; N]]) -> usize {
+    let length: f32 = points
+        .windows(2)
+        .map(|p| distance([p[0][0], p[0][1]], [p[1][0], p[1][1]]))
+        .sum();
+    ((length / 4.0).ceil() as usize).clamp(CURVE_SEGMENTS, MAX_CURVE_SEGMENTS)
+}
+
+/// Add up points, each multiplied by a weight.
+fn blend<const N: usize>(points: &[[f32; N]], weights: &[f32]) -> [f32; N] {
+    let mut sum = [0.0; N];
+    for (point, weight) in points.iter().zip(weights) {
+        for (total, value) in sum.iter_mut().zip(point) {
+            *total += value * weight;
+        }
+    }
+    sum
+}
+
+/// Flatten path operations into subpaths of points, and whether each is
+/// closed.  `vertex` gives the values of each vertex, starting with its
+/// position, and values between vertices are interpolated along curves the
+/// same way positions are.  Closed subpaths end with their first point.
+pub(crate) fn flatten_with<const N: usize, F>(
+    ops: &[PathOp],
+    vertex: F,
+) -> Option<Vec<(Vec<[f32; N]>, bool)>>
+where
+    F: Fn(u32) -> Option<[f32; N]>,
+{
+    let mut subpaths = Vec::new();
+    let mut points: Vec<[f32; N]> = Vec::new();
+    let mut pen = [0.0; N];
+    for op in ops {
+        match *op {
+            PathOp::Close() => {
+                if let Some(&first) = points.first() {
+                    points.push(first);
+                    pen = first;
+                    subpaths.push((std::mem::take(&mut points), true));
+                }
+            }
+            PathOp::Move(a) => {
+                if points.len() > 1 {
+                    subpaths.push((std::mem::take(&mut points), false));
+                }
+                pen = vertex(a)?;
+                points = vec![pen];
+            }
+            PathOp::Line(a) => {
+                if points.is_empty() {
+                    points.push(pen);
+                }
+                pen = vertex(a)?;
+                points.push(pen);
+            }
+            PathOp::Quad(a, b) => {
+                if points.is_empty() {
+                    points.push(pen);
+                }
+                let control = [pen, vertex(a)?, vertex(b)?];
+                let segments = curve_segments(&control);
+                for i in 1..=segments {
+                    let t = i as f32 / segments as f32;
+                    let u = 1.0 - t;
+                    points.push(blend(&control, &[u * u, 2.0 * u * t, t * t]));
+                }
+                pen = control[2];
+            }
+            PathOp::Cubic(a, b, c) => {
+                if points.is_empty() {
+                    points.push(pen);
+                }
+                let control = [pen, vertex(a)?, vertex(b)?, vertex(c)?];
+                let segments = curve_segments(&control);
+                for i in 1..=segments {
+                    let t = i as f32 / segments as f32;
+                    let u = 1.0 - t;
+                    let weights = [
+                        u * u * u,
+                        3.0 * u * u * t,
+                        3.0 * u * t * t,
+                        t * t * t,
+                    ];
+                    points.push(blend(&control, &weights));
+                }
+                pen = control[3];
+            }
+        }
+    }
+    if points.len() > 1 {
+        subpaths.push((points, false));
+    }
+    Some(subpaths)
+}
+
+/// Flatten path operations into contours, with `vertex` giving the position
+/// of each vertex.
+pub(crate) fn flatten<F>(ops: &[PathOp], vertex: F) -> Option<Vec<Contour>>
+where
+    F: Fn(u32) -> Option<[f32; 2]>,
+{
+    let subpaths = flatten_with(ops, vertex)?;
+    Some(
+        subpaths
+            .into_iter()
+            .map(|(points, closed)| Contour { points, closed })
+            .collect(),
+    )
+}
+
+impl Graphic {
+    /// Flatten a path in `group` into contours, using the positions of its
+    /// vertices in `vertex_list`.
+    pub fn contours(&self, path: u32) -> Option<Vec<Contour>> {
+        flatten(self.group.get(path as usize)?, |index| self.vertex(index))
+    }
+
+    /// Get the point at a normalized distance (0 to 1) along the arc length
+    /// of a path in `group`, and the unit tangent there.  Subpaths are
+    /// followed one after another.
+    pub fn point_along(
+        &self,
+        path: u32,
+        distance: f32,
+    ) -> Option<([f32; 2], [f32; 2])> {
+        self.point_along_with(path, distance, |index| self.vertex(index))
+    }
+
+    /// Get the point and tangent at a distance along a path, as
+    /// `point_along()` does, with `vertex` giving the position of each
+    /// vertex.
+    pub(crate) fn point_along_with<F>(
+        &self,
+        path: u32,
+        distance: f32,
+        vertex: F,
+    ) -> Option<([f32; 2], [f32; 2])>
+    where
+        F: Fn(u32) -> Option<[f32; 2]>,
+    {
+        let contours = flatten(self.group.get(path as usize)?, vertex)?;
+        let total: f32 = contours.iter().map(Contour::length).sum();
+        let mut left = distance.clamp(0.0, 1.0) * total;
+        let mut found = None;
+        for contour in &contours {
+            let length = contour.length();
+            found = contour.point_at(left);
+            if left <= length {
+                break;
+            }
+            left -= length;
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Model;
+
+    /// A graphic with an L-shaped path from (0, 0) to (10, 0) to (10, 10),
+    /// and a second subpath from (20, 0) to (20, 20).
+    fn graphic() -> Graphic {
+        Graphic {
+            attributes: vec![],
+            vertex_list: vec![
+                0.0, 0.0, 10.0, 0.0, 10.0, 10.0, 20.0, 0.0, 20.0, 20.0,
+            ],
+            group: vec![
+                vec![PathOp::Move(0), PathOp::Line(1), PathOp::Line(2)],
+                vec![
+                    PathOp::Move(0),
+                    PathOp::Line(1),
+                    PathOp::Move(3),
+                    PathOp::Line(4),
+                ],
+            ],
+            gradients: vec![],
+            models: Vec::<Model>::new(),
+            bitmaps: vec![],
+        }
+    }
+
+    #[test]
+    fn contour_length() {
+        let contours = graphic().contours(1).unwrap();
+        assert_eq!(contours.len(), 2);
+        assert_eq!(contours[0].length(), 10.0);
+        assert_eq!(contours[1].length(), 20.0);
+        assert!(!contours[0].closed);
+        assert!(graphic().contours(2).is_none());
+    }
+
+    #[test]
+    fn point_along_distance() {
+        let graphic = graphic();
+        let along = |path, distance| graphic.point_along(path, distance);
+        assert_eq!(along(0, 0.25), Some(([5.0, 0.0], [1.0, 0.0])));
+        assert_eq!(along(0, 0.75), Some(([10.0, 5.0], [0.0, 1.0])));
+        // Distances are clamped to the path.
+        assert_eq!(along(0, -1.0), Some(([0.0, 0.0], [1.0, 0.0])));
+        assert_eq!(along(0, 1.5), Some(([10.0, 10.0], [0.0, 1.0])));
+        // Subpaths are followed one after another, by arc length.
+        assert_eq!(along(1, 0.5), Some(([20.0, 5.0], [0.0, 1.0])));
+    }
+
+    fn square() -> Contour {
+        Contour {
+            points: vec![
+                [0.0, 0.0],
+                [10.0, 0.0],
+                [10.0, 10.0],
+                [0.0, 10.0],
+                [0.0, 0.0],
+            ],
+            closed: true,
+        }
+    }
+
+    fn assert_dashes(dashes: &[Contour], expected: &[&[[f32; 2]]]) {
+        assert_eq!(dashes.len(), expected.len(), "{:?}", dashes);
+        for (dash, expected) in dashes.iter().zip(expected) {
+            assert!(!dash.closed);
+            assert_eq!(dash.points.len(), expected.len(), "{:?}", dash);
+            for (point, expected) in dash.points.iter().zip(*expected) {
+                let near = (point[0] - expected[0]).abs() < 1e-4
+                    && (point[1] - expected[1]).abs() < 1e-4;
+                assert!(near, "{:?} is not {:?}", point, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn dash_square() {
+        let dashes = square().dash(&[5.0, 5.0], 0.0);
+        assert_dashes(
+            &dashes,
+            &[
+                &[[0.0, 0.0], [5.0, 0.0]],
+                &[[10.0, 0.0], [10.0, 5.0]],
+                &[[10.0, 10.0], [5.0, 10.0]],
+                &[[0.0, 10.0], [0.0, 5.0]],
+            ],
+        );
+    }
+
+    #[test]
+    fn dash_across_start() {
+        let dashes = square().dash(&[5.0, 5.0], 2.0);
+        assert_dashes(
+            &dashes,
+            &[
+                &[[0.0, 2.0], [0.0, 0.0], [3.0, 0.0]],
+                &[[8.0, 0.0], [10.0, 0.0], [10.0, 3.0]],
+                &[[10.0, 8.0], [10.0, 10.0], [7.0, 10.0]],
+                &[[2.0, 10.0], [0.0, 10.0], [0.0, 7.0]],
+            ],
+        );
+    }
+
+    #[test]
+    fn dash_offset_past_pattern() {
+        let dashes = square().dash(&[5.0, 5.0], 22.0);
+        assert_eq!(dashes, square().dash(&[5.0, 5.0], 2.0));
+    }
+
+    #[test]
+    fn dash_odd_pattern() {
+        let line = Contour {
+            points: vec![[0.0, 0.0], [12.0, 0.0]],
+            closed: false,
+        };
+        let dashes = line.dash(&[2.0, 1.0, 3.0], 0.0);
+        assert_dashes(
+            &dashes,
+            &[
+                &[[0.0, 0.0], [2.0, 0.0]],
+                &[[3.0, 0.0], [6.0, 0.0]],
+                &[[8.0, 0.0], [9.0, 0.0]],
+            ],
+        );
+    }
+
+    #[test]
+    fn dash_values() {
+        // The value after the position is interpolated along with it.
+        let points = [[0.0, 0.0, 0.0], [4.0, 0.0, 8.0], [4.0, 4.0, 0.0]];
+        let dashes = dash_with(&points, false, &[3.0, 2.0], 0.0);
+        assert_eq!(
+            dashes,
+            vec![
+                (vec![[0.0, 0.0, 0.0], [3.0, 0.0, 6.0]], false),
+                (vec![[4.0, 1.0, 6.0], [4.0, 4.0, 0.0]], false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{Bitmap, Graphic};
+use pix::{
+    gray::{SGray8, SGraya8},
+    rgb::{SRgb8, SRgba8},
+    Raster,
+};
+use png_pong::{FrameDecoder, FrameEncoder};
+use std::io::{Cursor, Read, Write};
+
+/// Decode the first frame of a PNG file in one specific pixel format.
+fn decode<F>(png: &[u8]) -> Option<Raster<SRgba8>>
+where
+    F: png_pong::Format<Chan = pix::chan::Ch8>,
+{
+    let frame = FrameDecoder::<_, F>::new(Cursor::new(png)).next()?.ok()?;
+    Some(Raster::with_raster(&frame.raster))
+}
+
+impl Bitmap {
+    /// Import a PNG file as a bitmap.  8-bit RGB(A) and grayscale(-alpha)
+    /// PNGs are supported.
+    pub fn from_png<R: Read>(mut reader: R) -> Option<Bitmap> {
+        let mut png = Vec::new();
+        reader.read_to_end(&mut png).ok()?;
+        let raster = decode::<SRgba8>(&png)
+            .or_else(|| decode::<SRgb8>(&png))
+            .or_else(|| decode::<SGraya8>(&png))
+            .or_else(|| decode::<SGray8>(&png))?;
+        Bitmap::from_raster(&raster)
+    }
+
+    /// Export this bitmap as an RGBA PNG file.
+    pub fn save_png<W: Write>(&self, writer: W) -> Option<()> {
+        FrameEncoder::<_, SRgba8>::new(writer)
+            .still(&self.to_raster()?)
+            .ok()
+    }
+}
+
+impl Graphic {
+    /// Export every embedded bitmap as a PNG file, calling `writer` to get
+    /// the destination for each bitmap index.  References are skipped.
+    pub fn save_bitmaps_png<W, F>(&self, mut writer: F) -> Option<()>
+    where
+        W: Write,
+        F: FnMut(usize) -> Option<W>,
+    {
+        for (i, bitmap) in self.bitmaps.iter().enumerate() {
+            if let Some(bitmap) = bitmap.embedded() {
+                bitmap.save_png(writer(i)?)?;
+            }
+        }
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_round_trip() {
+        let bitmap = Bitmap {
+            width: 2,
+            height: 2,
+            srgba: vec![
+                0xFF, 0x00, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0x80, //
+                0x00, 0x00, 0xFF, 0x40, 0xFF, 0xFF, 0xFF, 0x00,
+            ],
+        };
+        let mut png = Vec::new();
+        bitmap.save_png(&mut png).unwrap();
+        let copy = Bitmap::from_png(png.as_slice()).unwrap();
+        assert_eq!((copy.width, copy.height), (2, 2));
+        assert_eq!(copy.srgba, bitmap.srgba);
+    }
+
+    #[test]
+    fn save_png_wrong_length() {
+        let bitmap = Bitmap {
+            width: 2,
+            height: 2,
+            srgba: vec![0; 12],
+        };
+        assert!(bitmap.save_png(Vec::new()).is_none());
+    }
+}
@@ -1,9 +1,22 @@
-use crate::Graphic;
+use crate::shade::Shading;
+use crate::{
+    Affine2, Attribute, Bitmap, BitmapSource, Gradient, Graphic, Model, Pose,
+    ResourceResolver, Wrap,
+};
 use footile::{Path2D, Plotter};
 use pix::{
-    chan::Ch8, el::Pixel, matte::Matte8, ops::SrcOver, rgb::SRgba8, Raster,
-    Region,
+    chan::{Ch8, Channel},
+    el::Pixel,
+    matte::Matte8,
+    ops::SrcOver,
+    rgb::{Rgba8p, SRgba8},
+    Raster, Region,
 };
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Widest and tallest a pattern tile is rendered, in pixels.
+const MAX_TILE: f32 = 2048.0;
 
 pub struct ScaledRegion {
     x: f32,
@@ -35,14 +48,199 @@ impl From<(f32, f32, f32, f32)> for ScaledRegion {
     }
 }
 
+/// Render the first frame of a graphic's only model.
 pub fn render<P, R>(raster: &mut Raster<P>, graphic: &Graphic, region: R)
 where
     R: Into<ScaledRegion>,
     P: Pixel<Alpha = pix::chan::Premultiplied, Gamma = pix::chan::Linear>,
     P::Chan: From<Ch8>,
+{
+    assert!(graphic.models.len() == 1);
+
+    render_at(raster, graphic, 0, 0.0, region);
+}
+
+/// Render a model of a graphic as it is `time_ms` milliseconds into its
+/// animation.  Returns `None` if the graphic has no model at index `model`.
+/// Glyph IDs, and bitmaps that aren't embedded, aren't rendered.
+pub fn render_at<P, R>(
+    raster: &mut Raster<P>,
+    graphic: &Graphic,
+    model: usize,
+    time_ms: f32,
+    region: R,
+) -> Option<()>
+where
+    R: Into<ScaledRegion>,
+    P: Pixel<Alpha = pix::chan::Premultiplied, Gamma = pix::chan::Linear>,
+    P::Chan: From<Ch8>,
+{
+    let mut cache = Cache::new(time_ms, None);
+    render_model(raster, graphic, model, region.into(), &mut cache)
+}
+
+/// Render a model of a graphic as `render_at()` does, loading the bitmaps it
+/// references from `resolver` as they are drawn.
+pub fn render_with<P, R, S>(
+    raster: &mut Raster<P>,
+    graphic: &Graphic,
+    model: usize,
+    time_ms: f32,
+    region: R,
+    resolver: &S,
+) -> Option<()>
+where
+    R: Into<ScaledRegion>,
+    P: Pixel<Alpha = pix::chan::Premultiplied, Gamma = pix::chan::Linear>,
+    P::Chan: From<Ch8>,
+    S: ResourceResolver,
+{
+    let mut cache = Cache::new(time_ms, Some(resolver));
+    render_model(raster, graphic, model, region.into(), &mut cache)
+}
+
+/// Check whether a model changes over time, either by its own keyframes or
+/// those of the models it is filled with tiles of.
+fn is_animated(
+    graphic: &Graphic,
+    model: usize,
+    visited: &mut Vec<usize>,
+) -> bool {
+    let m = match graphic.models.get(model) {
+        Some(m) if !visited.contains(&model) => m,
+        _ => return false,
+    };
+    visited.push(model);
+    m.frames.len() > 1
+        || (0..m.groups.len())
+            .filter_map(|group| m.group_pattern(group))
+            .any(|pattern| is_animated(graphic, pattern as usize, visited))
+}
+
+/// Bitmaps made while rendering: tiles of the models that groups are filled
+/// with patterns of, and bitmaps loaded by a resolver.  Tiles of models that
+/// don't change over time are kept from one time to the next, such as across
+/// the frames of `render_clip()`.
+struct Cache<'a> {
+    /// Time into the animation that tiles are rendered at
+    time_ms: f32,
+    /// Models being rendered, which can't be tiled within themselves
+    drawing: Vec<usize>,
+    /// Each tile, by model, then tile size, spacing and size in pixels, then
+    /// the time it was rendered at, for animated models
+    tiles: HashMap<(u32, [u32; 6], Option<u32>), Option<Bitmap>>,
+    /// Resolver for bitmaps that aren't embedded
+    resolver: Option<&'a dyn ResourceResolver>,
+    /// Each bitmap loaded by the resolver, by index in `Graphic::bitmaps`
+    resolved: HashMap<u32, Option<Rc<Bitmap>>>,
+}
+
+impl<'a> Cache<'a> {
+    /// Start a cache of tiles, rendered `time_ms` milliseconds into the
+    /// animation, and of bitmaps loaded by `resolver`.
+    fn new(time_ms: f32, resolver: Option<&'a dyn ResourceResolver>) -> Self {
+        Cache {
+            time_ms,
+            drawing: Vec::new(),
+            tiles: HashMap::new(),
+            resolver,
+            resolved: HashMap::new(),
+        }
+    }
+
+    /// Get a bitmap that isn't embedded, loading it the first time.
+    fn resolve(&mut self, graphic: &Graphic, index: u32) -> Option<Rc<Bitmap>> {
+        let resolver = self.resolver?;
+        self.resolved
+            .entry(index)
+            .or_insert_with(|| {
+                let source = graphic.bitmaps.get(index as usize)?;
+                source.resolve(resolver).map(Rc::new)
+            })
+            .clone()
+    }
+
+    /// Move on to rendering tiles at another time, forgetting the tiles of
+    /// animated models.
+    fn set_time(&mut self, time_ms: f32) {
+        if time_ms.to_bits() != self.time_ms.to_bits() {
+            self.tiles.retain(|(_, _, time), _| time.is_none());
+            self.time_ms = time_ms;
+        }
+    }
+
+    /// Get the tile of a model, with a tile `size` and `spacing` in pattern
+    /// space, rendered at the resolution `to_pixel` draws it at.  The tile
+    /// comes with the transform from pixels to the tile's bitmap.
+    fn tile(
+        &mut self,
+        graphic: &Graphic,
+        model: u32,
+        size: Option<(f32, f32)>,
+        (gap_x, gap_y): (f32, f32),
+        to_pixel: Affine2,
+    ) -> Option<(&Bitmap, Affine2)> {
+        let m = graphic.models.get(model as usize)?;
+        if self.drawing.contains(&(model as usize)) {
+            return None;
+        }
+        let (width, height) = size.unwrap_or((m.width, m.height));
+        let (cell_x, cell_y) = (width + gap_x, height + gap_y);
+        let valid = [m.width, m.height, width, height, cell_x, cell_y]
+            .iter()
+            .all(|length| length.is_finite() && *length > 0.0);
+        if !valid {
+            return None;
+        }
+        let to_tile = to_pixel.inverse()?;
+        let pixels = |length: f32, (x, y): (f32, f32)| {
+            (length * x.hypot(y)).ceil().clamp(1.0, MAX_TILE)
+        };
+        let px = pixels(cell_x, (to_pixel.a, to_pixel.b));
+        let py = pixels(cell_y, (to_pixel.c, to_pixel.d));
+        let key = (
+            model,
+            [
+                width.to_bits(),
+                height.to_bits(),
+                gap_x.to_bits(),
+                gap_y.to_bits(),
+                px as u32,
+                py as u32,
+            ],
+            Some(self.time_ms.to_bits())
+                .filter(|_| is_animated(graphic, model as usize, &mut vec![])),
+        );
+        if !self.tiles.contains_key(&key) {
+            // The tile is drawn at the top left of its cell, clipped to it.
+            let mut raster = Raster::<Rgba8p>::with_clear(px as u32, py as u32);
+            let xs = px / cell_x * width / m.width;
+            let ys = py / cell_y * height / m.height;
+            let region = ScaledRegion::new(0.0, 0.0, xs * px, ys * py);
+            render_model(&mut raster, graphic, model as usize, region, self)?;
+            let tile = Bitmap::from_raster(&Raster::with_raster(&raster));
+            self.tiles.insert(key, tile);
+        }
+        let tile = self.tiles.get(&key)?.as_ref()?;
+        Some((tile, to_tile.then(Affine2::scale(px / cell_x, py / cell_y))))
+    }
+}
+
+/// Render a model of a graphic, as `render_at()` does, with a cache of the
+/// bitmaps it draws.
+fn render_model<P>(
+    raster: &mut Raster<P>,
+    graphic: &Graphic,
+    model: usize,
+    region: ScaledRegion,
+    cache: &mut Cache,
+) -> Option<()>
+where
+    P: Pixel<Alpha = pix::chan::Premultiplied, Gamma = pix::chan::Linear>,
+    P::Chan: From<Ch8>,
 {
     let (xs, ys, dst_region): (_, _, Region) = {
-        let r: ScaledRegion = region.into();
+        let r = region;
         if r.width.is_infinite() || r.height.is_infinite() {
             (
                 1.0,
@@ -60,83 +258,423 @@ where
         }
     };
 
-    // We can't render these types of RVGs with footile yet.
-    assert!(graphic.attributes.is_empty());
-    assert!(graphic.bitmaps.is_empty());
-    assert!(graphic.models.len() == 1);
+    let state = graphic.sample(model, cache.time_ms)?;
+    cache.drawing.push(model);
+    let model = &graphic.models[model];
+    let scale = (xs, ys);
 
-    let model = &graphic.models[0];
-    let mut p = Plotter::new(Raster::with_clear(
-        model.width as u32,
-        model.height as u32,
-    ));
+    // Frames left on the canvas by `Animation::Layer`.
+    for pose in &state.layers {
+        render_pose(raster, graphic, model, pose, scale, dst_region, cache);
+    }
 
-    for (group_id, group_props) in &model.groups {
-        let mut path = Path2D::default().absolute();
+    if let Some((next, amount)) = &state.fade {
+        // Cross-fade between the two poses, then composite the result.
+        let mut from = Raster::<P>::with_clear(raster.width(), raster.height());
+        let mut to = Raster::<P>::with_clear(raster.width(), raster.height());
+        render_pose(
+            &mut from,
+            graphic,
+            model,
+            &state.pose,
+            scale,
+            dst_region,
+            cache,
+        );
+        render_pose(&mut to, graphic, model, next, scale, dst_region, cache);
+        let amount = <P::Chan as From<f32>>::from(*amount);
+        for (from, to) in from.pixels_mut().iter_mut().zip(to.pixels()) {
+            let mut channels = [P::Chan::MIN; 4];
+            for (i, (a, b)) in
+                from.channels().iter().zip(to.channels()).enumerate()
+            {
+                channels[i] = a.lerp(*b, amount);
+            }
+            *from = P::from_channels(&channels[..from.channels().len()]);
+        }
+        raster.composite_raster((), &from, (), SrcOver);
+    } else {
+        let pose = &state.pose;
+        render_pose(raster, graphic, model, pose, scale, dst_region, cache);
+    }
+    cache.drawing.pop();
+    Some(())
+}
+
+/// A model rendered into frames at a fixed frame rate, for export.
+pub struct Clip {
+    /// Each frame, and how many milliseconds it is shown for.  Runs of
+    /// identical frames are merged into one.
+    pub frames: Vec<(Raster<SRgba8>, u32)>,
+    /// Number of times to play the frames, or 0 to loop forever
+    pub loops: u32,
+    /// Frames a second the model was sampled at
+    pub fps: f32,
+}
+
+/// Get the number of samples in `span` milliseconds, at `fps`.
+fn sample_count(span: u64, fps: f32) -> u64 {
+    ((span as f32 * fps / 1000.0).ceil() as u64).max(1)
+}
 
-        println!("Building Path….");
+/// Get the time in milliseconds of a sample, at `fps`, in a clip `span`
+/// milliseconds long.
+fn sample_time(i: u64, fps: f32, span: u64) -> f32 {
+    (i as f32 * 1000.0 / fps).round().min(span as f32)
+}
+
+impl Clip {
+    /// Get the frame shown at each sample, repeating merged frames, for
+    /// formats with a constant frame rate.
+    pub fn samples(&self) -> impl Iterator<Item = &Raster<SRgba8>> + '_ {
+        let span: u64 = self.frames.iter().map(|(_, d)| u64::from(*d)).sum();
+        let mut index = 0;
+        let mut end = self.frames.first().map_or(0.0, |(_, d)| *d as f32);
+        (0..sample_count(span, self.fps)).filter_map(move |i| {
+            let time = sample_time(i, self.fps, span);
+            while time >= end && index + 1 < self.frames.len() {
+                index += 1;
+                end += self.frames[index].1 as f32;
+            }
+            self.frames.get(index).map(|(raster, _)| raster)
+        })
+    }
+}
+
+/// Render a model of a graphic into frames, sampled `fps` times a second.
+///
+/// Repeating models are rendered for one loop (or a back and forth for
+/// ping-pong), to be played `Clip::loops` times; models that loop forever
+/// include their start delay in every loop.  Others are rendered from start
+/// to end, to be played once.
+pub fn render_clip(graphic: &Graphic, model: usize, fps: f32) -> Option<Clip> {
+    let m = graphic.models.get(model)?;
+    if fps.is_nan() || fps <= 0.0 {
+        return None;
+    }
+    let playback = &m.playback;
+    let cycle = if playback.ping_pong { 2 } else { 1 };
+    let cycle_ms = u64::from(m.duration()) * cycle;
+    let (span, loops) = match m.total_duration() {
+        None => (u64::from(playback.start_delay) + cycle_ms, 0),
+        Some(_)
+            if playback.start_delay == 0
+                && u64::from(playback.loops) % cycle == 0 =>
+        {
+            (cycle_ms, (u64::from(playback.loops) / cycle) as u32)
+        }
+        Some(total) => (total, 1),
+    };
+
+    let (width, height) = (m.width as u32, m.height as u32);
+    let count = sample_count(span, fps);
+    let time = |i: u64| sample_time(i, fps, span);
+    let mut frames: Vec<(Raster<SRgba8>, u32)> = Vec::new();
+    let mut cache = Cache::new(0.0, None);
+    for i in 0..count {
+        let mut raster = Raster::<Rgba8p>::with_clear(width, height);
+        cache.set_time(time(i));
+        render_model(&mut raster, graphic, model, ().into(), &mut cache)?;
+        let raster = Raster::<SRgba8>::with_raster(&raster);
+        let delay = if i + 1 == count {
+            span as f32 - time(i)
+        } else {
+            time(i + 1) - time(i)
+        } as u32;
+        match frames.last_mut() {
+            Some((last, last_delay))
+                if last.as_u8_slice() == raster.as_u8_slice() =>
+            {
+                *last_delay += delay;
+            }
+            _ => frames.push((raster, delay)),
+        }
+    }
+    Some(Clip { frames, loops, fps })
+}
+
+/// The ends of the unclosed subpaths of a path, for drawing line caps.
+#[derive(Default)]
+struct Ends {
+    /// Each end, and the direction pointing out of the subpath there
+    ends: Vec<((f32, f32), (f32, f32))>,
+    /// Start of the current subpath
+    start: (f32, f32),
+    /// Direction the current subpath starts in
+    first: Option<(f32, f32)>,
+    /// End of the last segment
+    pen: (f32, f32),
+    /// Direction the last segment ends in
+    last: Option<(f32, f32)>,
+    /// Whether the current subpath has any segments
+    drawn: bool,
+}
+
+/// Get the unit vector from `a` to `b`, if they are different points.
+fn direction(a: (f32, f32), b: (f32, f32)) -> Option<(f32, f32)> {
+    let (x, y) = (b.0 - a.0, b.1 - a.1);
+    let len = x.hypot(y);
+    if len > 0.0 {
+        Some((x / len, y / len))
+    } else {
+        None
+    }
+}
+
+impl Ends {
+    /// Start a new subpath.
+    fn move_to(&mut self, point: (f32, f32)) {
+        self.finish();
+        self.start = point;
+        self.pen = point;
+        self.first = None;
+        self.last = None;
+    }
+
+    /// Add a segment from the pen through control points to an end point.
+    fn segment(&mut self, points: &[(f32, f32)]) {
+        let end = points[points.len() - 1];
+        let first = points.iter().find_map(|p| direction(self.pen, *p));
+        let last = points[..points.len() - 1]
+            .iter()
+            .rev()
+            .chain(std::iter::once(&self.pen))
+            .find_map(|p| direction(*p, end));
+        self.first = self.first.or(first);
+        self.last = last.or(self.last);
+        self.pen = end;
+        self.drawn = true;
+    }
+
+    /// Close the current subpath, which leaves it without ends.
+    fn close(&mut self) {
+        self.drawn = false;
+        self.move_to(self.start);
+    }
+
+    /// Record the ends of the current subpath, if it is unclosed.
+    fn finish(&mut self) {
+        if self.drawn {
+            let (sx, sy) = self.first.or(self.last).unwrap_or((1.0, 0.0));
+            let end = self.last.or(self.first).unwrap_or((1.0, 0.0));
+            self.ends.push((self.start, (-sx, -sy)));
+            self.ends.push((self.pen, end));
+            self.drawn = false;
+        }
+    }
 
-        let mut fill_color = SRgba8::new(0, 0, 0, 0);
-        let mut stroke_color = SRgba8::new(0, 0, 0, 0);
-        for prop in group_props {
-            use crate::GroupProperty::*;
-            match *prop {
-                FillColorRgba([red, green, blue, alpha]) => {
-                    fill_color = SRgba8::new(red, green, blue, alpha)
+    /// Build the shapes of a line cap at every end, for a stroke with
+    /// half-width `r`.
+    fn caps(mut self, cap: crate::LineCap, r: f32) -> Vec<footile::PathOp> {
+        self.finish();
+        let mut path = Path2D::default().absolute();
+        for ((x, y), (dx, dy)) in self.ends {
+            match cap {
+                crate::LineCap::Butt => {}
+                crate::LineCap::Round => {
+                    // A circle, from four cubic curves.
+                    let k = 0.552_284_8 * r;
+                    path = path
+                        .move_to(x + r, y)
+                        .cubic_to(x + r, y + k, x + k, y + r, x, y + r)
+                        .cubic_to(x - k, y + r, x - r, y + k, x - r, y)
+                        .cubic_to(x - r, y - k, x - k, y - r, x, y - r)
+                        .cubic_to(x + k, y - r, x + r, y - k, x + r, y)
+                        .close();
                 }
-                StrokeColorRgba([red, green, blue, alpha]) => {
-                    stroke_color = SRgba8::new(red, green, blue, alpha)
+                crate::LineCap::Square => {
+                    // Flush with the end, and reaching `r` past it.
+                    let (nx, ny) = (-dy * r, dx * r);
+                    let (fx, fy) = (x + dx * r, y + dy * r);
+                    path = path
+                        .move_to(x + nx, y + ny)
+                        .line_to(fx + nx, fy + ny)
+                        .line_to(fx - nx, fy - ny)
+                        .line_to(x - nx, y - ny)
+                        .close();
                 }
-                StrokeWidth(w) => path = path.pen_width(w),
-                JoinStyle(_) => unimplemented!(),
-                FillRule(_) => unimplemented!(),
-                GlyphID(_) => unimplemented!(),
-                BitmapPattern(_) => unimplemented!(),
-                GroupPattern(_) => unimplemented!(),
             }
         }
+        path.finish()
+    }
+}
+
+/// Build the dashes of a path, flattened where it is drawn, to stroke with
+/// width `width`, and their ends.
+fn dashes<F>(
+    ops: &[crate::PathOp],
+    vertex: F,
+    (pattern, offset): (&[f32], f32),
+    width: f32,
+) -> (Vec<footile::PathOp>, Ends)
+where
+    F: Fn(u32) -> Option<[f32; 2]>,
+{
+    let mut path = Path2D::default().absolute().pen_width(width);
+    let mut ends = Ends::default();
+    let contours = crate::path::flatten(ops, vertex).unwrap_or_default();
+    for dash in contours.iter().flat_map(|c| c.dash(pattern, offset)) {
+        let mut points = dash.points.iter().map(|&[x, y]| (x, y));
+        if let Some((x, y)) = points.next() {
+            ends.move_to((x, y));
+            path = path.move_to(x, y);
+        }
+        for (x, y) in points {
+            ends.segment(&[(x, y)]);
+            path = path.line_to(x, y);
+        }
+        if dash.closed {
+            ends.close();
+            path = path.close();
+        }
+    }
+    (path.finish(), ends)
+}
+
+/// Composite a matte onto a raster, painted with the sRGBA color `paint`
+/// gives at the center of each pixel, and faded by `opacity`.
+fn composite_paint<P, F>(
+    raster: &mut Raster<P>,
+    dst_region: Region,
+    matte: &Raster<Matte8>,
+    opacity: f32,
+    paint: F,
+) where
+    P: Pixel<Alpha = pix::chan::Premultiplied, Gamma = pix::chan::Linear>,
+    P::Chan: From<Ch8>,
+    F: Fn(f32, f32) -> [u8; 4],
+{
+    let opacity = opacity.clamp(0.0, 1.0);
+    let width = matte.width() as usize;
+    let mut src = Raster::<SRgba8>::with_clear(matte.width(), matte.height());
+    for (i, (pixel, coverage)) in
+        src.pixels_mut().iter_mut().zip(matte.pixels()).enumerate()
+    {
+        let coverage = u8::from(coverage.alpha());
+        if coverage == 0 {
+            continue;
+        }
+        let (x, y) = ((i % width) as f32 + 0.5, (i / width) as f32 + 0.5);
+        let [red, green, blue, alpha] = paint(x, y);
+        let alpha = f32::from(alpha) * f32::from(coverage) / 255.0 * opacity;
+        *pixel = SRgba8::new(red, green, blue, alpha.round() as u8);
+    }
+    raster.composite_raster(
+        dst_region,
+        &Raster::<P>::with_raster(&src),
+        (),
+        SrcOver,
+    );
+}
+
+/// Composite a matte onto a raster, painted with a gradient that
+/// `to_pixel` maps onto the matte, and faded by `opacity`.
+fn composite_gradient<P>(
+    raster: &mut Raster<P>,
+    dst_region: Region,
+    matte: &Raster<Matte8>,
+    (gradient, to_pixel): (&Gradient, Affine2),
+    opacity: f32,
+) where
+    P: Pixel<Alpha = pix::chan::Premultiplied, Gamma = pix::chan::Linear>,
+    P::Chan: From<Ch8>,
+{
+    if let Some(to_gradient) = to_pixel.inverse() {
+        composite_paint(raster, dst_region, matte, opacity, |x, y| {
+            let (x, y) = to_gradient.apply(x, y);
+            gradient.color(gradient.offset_at([x, y]))
+        });
+    }
+}
 
-        for pathop in &graphic.group[*group_id as usize] {
+/// Render one pose of a model.
+fn render_pose<P>(
+    raster: &mut Raster<P>,
+    graphic: &Graphic,
+    model: &Model,
+    pose: &Pose,
+    (xs, ys): (f32, f32),
+    dst_region: Region,
+    cache: &mut Cache,
+) where
+    P: Pixel<Alpha = pix::chan::Premultiplied, Gamma = pix::chan::Linear>,
+    P::Chan: From<Ch8>,
+{
+    // footile fills rows 8 pixels at a time, past the end of narrower
+    // rasters, so the matte is padded to a multiple of 8 and clipped after.
+    let (width, height) =
+        ((model.width * xs) as u32, (model.height * ys) as u32);
+    let clip = Region::new(dst_region.left(), dst_region.top(), width, height);
+    let dst_region = dst_region.intersection(clip);
+    let mut p =
+        Plotter::new(Raster::with_clear(width + (8 - width % 8) % 8, height));
+
+    // Vertices moved by keyframe morphs.
+    let morphed = |index: u32| {
+        pose.vertices
+            .binary_search_by_key(&index, |(i, _)| *i)
+            .ok()
+            .map(|i| pose.vertices[i].1)
+    };
+
+    // Vertex colors shade fills painted with a color.
+    let shaded = graphic.attributes.iter().any(|attribute| {
+        matches!(
+            attribute,
+            Attribute::Rgb | Attribute::Rbga | Attribute::Alpha
+        )
+    });
+
+    for (i, (group_id, _)) in model.groups.iter().enumerate() {
+        let matrix = pose.transforms[i];
+        let vertex = |index: u32| {
+            let [x, y] =
+                morphed(index).or_else(|| graphic.vertex(index)).unwrap();
+            let (x, y) = matrix.apply(x, y);
+            (x * xs, y * ys)
+        };
+        let style = pose.styles[i];
+        // Strokes are as wide, and dashes as long, as the render is scaled.
+        let scale = (xs * ys).sqrt();
+        let stroke_width = style.stroke_width * scale;
+        let mut path = Path2D::default().absolute().pen_width(stroke_width);
+        let mut ends = Ends::default();
+
+        let color = |[red, green, blue, alpha]: [u8; 4]| {
+            let alpha = (f32::from(alpha) * style.opacity.clamp(0.0, 1.0))
+                .round() as u8;
+            SRgba8::new(red, green, blue, alpha)
+        };
+        let fill_color = color(style.fill);
+        let stroke_color = color(style.stroke);
+        let ops = &graphic.group[*group_id as usize];
+        for pathop in ops {
             match *pathop {
-                crate::PathOp::Close() => path = path.close(),
+                crate::PathOp::Close() => {
+                    ends.close();
+                    path = path.close();
+                }
                 crate::PathOp::Move(a) => {
-                    let (x, y) = (
-                        graphic.vertex_list[a as usize * 2] * xs,
-                        graphic.vertex_list[a as usize * 2 + 1] * ys,
-                    );
+                    let (x, y) = vertex(a);
+                    ends.move_to((x, y));
                     path = path.move_to(x, y);
                 }
                 crate::PathOp::Line(a) => {
-                    let (x, y) = (
-                        graphic.vertex_list[a as usize * 2] * xs,
-                        graphic.vertex_list[a as usize * 2 + 1] * ys,
-                    );
+                    let (x, y) = vertex(a);
+                    ends.segment(&[(x, y)]);
                     path = path.line_to(x, y);
                 }
                 crate::PathOp::Quad(a, b) => {
-                    let (bx, by) = (
-                        graphic.vertex_list[a as usize * 2] * xs,
-                        graphic.vertex_list[a as usize * 2 + 1] * ys,
-                    );
-                    let (cx, cy) = (
-                        graphic.vertex_list[b as usize * 2] * xs,
-                        graphic.vertex_list[b as usize * 2 + 1] * ys,
-                    );
+                    let (bx, by) = vertex(a);
+                    let (cx, cy) = vertex(b);
+                    ends.segment(&[(bx, by), (cx, cy)]);
                     path = path.quad_to(bx, by, cx, cy);
                 }
                 crate::PathOp::Cubic(a, b, c) => {
-                    let (bx, by) = (
-                        graphic.vertex_list[a as usize * 2] * xs,
-                        graphic.vertex_list[a as usize * 2 + 1] * ys,
-                    );
-                    let (cx, cy) = (
-                        graphic.vertex_list[b as usize * 2] * xs,
-                        graphic.vertex_list[b as usize * 2 + 1] * ys,
-                    );
-                    let (dx, dy) = (
-                        graphic.vertex_list[c as usize * 2] * xs,
-                        graphic.vertex_list[c as usize * 2 + 1] * ys,
-                    );
+                    let (bx, by) = vertex(a);
+                    let (cx, cy) = vertex(b);
+                    let (dx, dy) = vertex(c);
+                    ends.segment(&[(bx, by), (cx, cy), (dx, dy)]);
                     path = path.cubic_to(bx, by, cx, cy, dx, dy);
                 }
             }
@@ -144,52 +682,857 @@ where
 
         let path = path.finish();
 
-        if fill_color.alpha() != Ch8::new(0u8) {
-            let fill =
-                p.fill(footile::FillRule::NonZero, &path, Matte8::new(255));
-
-            let temp_raster: Raster<
-                pix::el::Pix1<
-                    P::Chan,
-                    pix::matte::Matte,
-                    pix::chan::Premultiplied,
-                    pix::chan::Linear,
-                >,
-            > = Raster::with_raster(fill);
-
-            raster.composite_matte(
-                dst_region,
-                &temp_raster,
-                (),
-                fill_color.convert(),
-                SrcOver,
-            );
+        // Gradients map from vertices, through the group, onto the matte.
+        let gradient = |index: Option<u32>| {
+            let gradient = graphic.gradients.get(index? as usize)?;
+            let to_pixel =
+                gradient.transform.then(matrix).then(Affine2::scale(xs, ys));
+            Some((gradient, to_pixel))
+        };
+        let fill_gradient = gradient(model.fill_gradient(i));
+        let stroke_gradient = gradient(model.stroke_gradient(i));
+
+        let resolved;
+        let pattern = match model.bitmap_pattern(i).and_then(|bitmap| {
+            Some((bitmap, graphic.bitmaps.get(bitmap as usize)?))
+        }) {
+            Some((_, BitmapSource::Embedded(bitmap))) => Some(bitmap),
+            Some((bitmap, _)) => {
+                resolved = cache.resolve(graphic, bitmap);
+                resolved.as_deref()
+            }
+            None => None,
+        };
+        let pattern_to_pixel = model
+            .pattern_transform(i)
+            .then(matrix)
+            .then(Affine2::scale(xs, ys));
+        let tile = match model.group_pattern(i) {
+            Some(tiled) if pattern.is_none() => cache.tile(
+                graphic,
+                tiled,
+                model.tile_size(i),
+                model.tile_spacing(i),
+                pattern_to_pixel,
+            ),
+            _ => None,
+        };
+
+        if pattern.is_some()
+            || tile.is_some()
+            || fill_gradient.is_some()
+            || fill_color.alpha() != Ch8::new(0u8)
+        {
+            let rule = match model.fill_rule(i) {
+                crate::FillRule::NonZero => footile::FillRule::NonZero,
+                crate::FillRule::EvenOdd => footile::FillRule::EvenOdd,
+            };
+            let fill = p.fill(rule, &path, Matte8::new(255));
+
+            // Shading falls back to a flat fill where vertices are missing.
+            let colors = |index| {
+                let [x, y] =
+                    morphed(index).or_else(|| graphic.vertex(index))?;
+                let (x, y) = matrix.apply(x, y);
+                let (x, y) = (x * xs, y * ys);
+                let mut color = style.fill.map(|c| f32::from(c) / 255.0);
+                if let Some(rgb) = graphic.attribute(index, Attribute::Rgb) {
+                    color[..3].copy_from_slice(rgb);
+                }
+                if let Some(rgba) = graphic.attribute(index, Attribute::Rbga) {
+                    color.copy_from_slice(rgba);
+                }
+                if let Some(alpha) = graphic.attribute(index, Attribute::Alpha)
+                {
+                    color[3] = alpha[0];
+                }
+                let [r, g, b, a] = color;
+                Some([x, y, r, g, b, a])
+            };
+            let shading = if shaded && fill_gradient.is_none() {
+                Shading::new(ops, colors, (fill.width(), fill.height()))
+            } else {
+                None
+            };
+            let (sampling, wrap) = (model.sampling(i), model.wrap(i));
+            let uv = graphic
+                .attributes
+                .contains(&Attribute::UvTextureCoordinates);
+            // Texture coordinates, from 0 to 1 across the bitmap.
+            let texture = match pattern {
+                Some(_) if uv => Shading::new(
+                    ops,
+                    |index| {
+                        let [x, y] =
+                            morphed(index).or_else(|| graphic.vertex(index))?;
+                        let (x, y) = matrix.apply(x, y);
+                        let uv = graphic.attribute(
+                            index,
+                            Attribute::UvTextureCoordinates,
+                        )?;
+                        Some([x * xs, y * ys, uv[0], uv[1]])
+                    },
+                    (fill.width(), fill.height()),
+                ),
+                _ => None,
+            };
+            // Otherwise, tiles of the bitmap, a unit for each pixel.
+            let to_bitmap = pattern_to_pixel.inverse().filter(|_| !uv);
+
+            if let (Some(bitmap), Some(texture)) = (pattern, &texture) {
+                let (width, height) =
+                    (f32::from(bitmap.width), f32::from(bitmap.height));
+                composite_paint(
+                    raster,
+                    dst_region,
+                    fill,
+                    style.opacity,
+                    |x, y| match texture.point(x, y) {
+                        Some([_, _, u, v]) => {
+                            bitmap.sample(u * width, v * height, sampling, wrap)
+                        }
+                        None => [0, 0, 0, 0],
+                    },
+                );
+            } else if let (Some(bitmap), Some(to_bitmap)) = (pattern, to_bitmap)
+            {
+                composite_paint(
+                    raster,
+                    dst_region,
+                    fill,
+                    style.opacity,
+                    |x, y| {
+                        let (x, y) = to_bitmap.apply(x, y);
+                        bitmap.sample(x, y, sampling, wrap)
+                    },
+                );
+            } else if let Some((tile, to_tile)) = tile {
+                // Tiles of a model, rendered as the pattern is drawn.
+                composite_paint(
+                    raster,
+                    dst_region,
+                    fill,
+                    style.opacity,
+                    |x, y| {
+                        let (x, y) = to_tile.apply(x, y);
+                        tile.sample(x, y, sampling, Wrap::Repeat)
+                    },
+                );
+            } else if let Some(gradient) = fill_gradient {
+                composite_gradient(
+                    raster,
+                    dst_region,
+                    fill,
+                    gradient,
+                    style.opacity,
+                );
+            } else if let Some(shading) = shading {
+                composite_paint(
+                    raster,
+                    dst_region,
+                    fill,
+                    style.opacity,
+                    |x, y| shading.color(x, y),
+                );
+            } else {
+                let temp_raster: Raster<
+                    pix::el::Pix1<
+                        P::Chan,
+                        pix::matte::Matte,
+                        pix::chan::Premultiplied,
+                        pix::chan::Linear,
+                    >,
+                > = Raster::with_raster(fill);
+
+                raster.composite_matte(
+                    dst_region,
+                    &temp_raster,
+                    (),
+                    fill_color.convert(),
+                    SrcOver,
+                );
+            }
             let mut pr = p.raster();
             pr.clear();
             p = Plotter::new(pr);
         }
-        if stroke_color.alpha() != Ch8::new(0u8) {
-            let stroke = p.stroke(&path, Matte8::new(255));
-
-            let temp_raster: Raster<
-                pix::el::Pix1<
-                    P::Chan,
-                    pix::matte::Matte,
-                    pix::chan::Premultiplied,
-                    pix::chan::Linear,
-                >,
-            > = Raster::with_raster(stroke);
-
-            raster.composite_matte(
-                dst_region,
-                &temp_raster,
-                (),
-                stroke_color.convert(),
-                SrcOver,
-            );
-            let mut pr = p.raster();
-            pr.clear();
-            p = Plotter::new(pr);
+        if stroke_gradient.is_some() || stroke_color.alpha() != Ch8::new(0u8) {
+            let dash = model.dash(i).map(|(pattern, offset)| {
+                let pattern: Vec<f32> =
+                    pattern.iter().map(|length| length * scale).collect();
+                (pattern, offset * scale)
+            });
+            let dash = dash
+                .as_ref()
+                .map(|(pattern, offset)| (&pattern[..], *offset));
+            // Each vertex has its own width, so the outline is built here
+            // and filled.  Without every width, the stroke is even.
+            let outline =
+                if graphic.attributes.contains(&Attribute::StrokeWidth) {
+                    crate::stroke::outline(
+                        ops,
+                        |index| {
+                            let (x, y) = vertex(index);
+                            let width = graphic
+                                .attribute(index, Attribute::StrokeWidth)?;
+                            Some([x, y, width[0] * scale])
+                        },
+                        (
+                            model.join_style(i),
+                            model.miter_limit(i),
+                            model.line_cap(i),
+                        ),
+                        dash,
+                    )
+                } else {
+                    None
+                };
+            let mut stroke = if let Some(outline) = outline {
+                p.fill(footile::FillRule::NonZero, &outline, Matte8::new(255));
+                p.raster()
+            } else {
+                p.set_join(match model.join_style(i) {
+                    crate::JoinStyle::Miter => {
+                        footile::JoinStyle::Miter(model.miter_limit(i))
+                    }
+                    crate::JoinStyle::Round => footile::JoinStyle::Round,
+                    crate::JoinStyle::Bevel => footile::JoinStyle::Bevel,
+                });
+                let (path, ends) = match dash {
+                    Some(dash) => dashes(
+                        ops,
+                        |index| {
+                            let (x, y) = vertex(index);
+                            Some([x, y])
+                        },
+                        dash,
+                        stroke_width,
+                    ),
+                    None => (path, ends),
+                };
+                p.stroke(&path, Matte8::new(255));
+                let mut stroke = p.raster();
+
+                // footile only strokes butt ends, so other caps are filled
+                // in.  Fills replace what is under them, so caps get their
+                // own matte.
+                let caps = ends.caps(model.line_cap(i), stroke_width / 2.0);
+                if !caps.is_empty() {
+                    let mut cp = Plotter::new(Raster::with_clear(
+                        stroke.width(),
+                        stroke.height(),
+                    ));
+                    cp.fill(
+                        footile::FillRule::NonZero,
+                        &caps,
+                        Matte8::new(255),
+                    );
+                    for (s, c) in
+                        stroke.pixels_mut().iter_mut().zip(cp.raster().pixels())
+                    {
+                        if c.alpha() > s.alpha() {
+                            *s = *c;
+                        }
+                    }
+                }
+                stroke
+            };
+
+            if let Some(gradient) = stroke_gradient {
+                composite_gradient(
+                    raster,
+                    dst_region,
+                    &stroke,
+                    gradient,
+                    style.opacity,
+                );
+            } else {
+                let temp_raster: Raster<
+                    pix::el::Pix1<
+                        P::Chan,
+                        pix::matte::Matte,
+                        pix::chan::Premultiplied,
+                        pix::chan::Linear,
+                    >,
+                > = Raster::with_raster(&stroke);
+
+                raster.composite_matte(
+                    dst_region,
+                    &temp_raster,
+                    (),
+                    stroke_color.convert(),
+                    SrcOver,
+                );
+            }
+            stroke.clear();
+            p = Plotter::new(stroke);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Animation, Frame, GroupProperty, Morph, PathOp, Playback, TransformOp,
+    };
+    use pix::rgb::Rgba8p;
+
+    /// A red 4 × 4 square, moved `xs` across an 8 × 8 model by each frame.
+    fn square(frames: Vec<(f32, u16, Animation)>) -> Graphic {
+        Graphic {
+            attributes: vec![],
+            vertex_list: vec![0.0, 0.0, 4.0, 0.0, 4.0, 4.0, 0.0, 4.0],
+            group: vec![vec![
+                PathOp::Move(0),
+                PathOp::Line(1),
+                PathOp::Line(2),
+                PathOp::Line(3),
+                PathOp::Close(),
+            ]],
+            gradients: vec![],
+            models: vec![Model {
+                width: 8.0,
+                height: 8.0,
+                groups: vec![(
+                    0,
+                    vec![GroupProperty::FillColorRgba([255, 0, 0, 255])],
+                )],
+                frames: frames
+                    .into_iter()
+                    .map(|(x, delay, animation)| Frame {
+                        transforms: vec![vec![TransformOp::Translate(
+                            x, 0.0, 0.0,
+                        )]],
+                        properties: vec![],
+                        morph: Morph::None,
+                        delay,
+                        animation,
+                    })
+                    .collect(),
+                playback: Playback::default(),
+            }],
+            bitmaps: vec![],
+        }
+    }
+
+    /// Render at a time, and get the red and alpha of pixels along y = 2.
+    fn row(graphic: &Graphic, time_ms: f32) -> Vec<(u8, u8)> {
+        let mut raster = Raster::<Rgba8p>::with_clear(8, 8);
+        render_at(&mut raster, graphic, 0, time_ms, ()).unwrap();
+        (0..8)
+            .map(|x| {
+                let p = raster.pixel(x, 2);
+                (u8::from(p.one()), u8::from(p.four()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn render_at_time() {
+        let graphic = square(vec![
+            (0.0, 100, Animation::Linear),
+            (4.0, 0, Animation::Done),
+        ]);
+        let row0 = row(&graphic, 0.0);
+        assert_eq!(row0[1], (255, 255));
+        assert_eq!(row0[5], (0, 0));
+        let row1 = row(&graphic, 50.0);
+        assert_eq!(row1[1], (0, 0));
+        assert_eq!(row1[3], (255, 255));
+        let row2 = row(&graphic, 100.0);
+        assert_eq!(row2[3], (0, 0));
+        assert_eq!(row2[5], (255, 255));
+    }
+
+    #[test]
+    fn render_at_fade_and_layer() {
+        let graphic = square(vec![
+            (0.0, 100, Animation::Layer),
+            (4.0, 100, Animation::Fade),
+            (0.0, 0, Animation::Done),
+        ]);
+        // The first frame stays under the second.
+        let row1 = row(&graphic, 100.0);
+        assert_eq!(row1[1], (255, 255));
+        assert_eq!(row1[5], (255, 255));
+
+        // Halfway through the fade, the second square is half faded into
+        // the third, which is drawn over the first.
+        let row2 = row(&graphic, 150.0);
+        assert_eq!(row2[1], (255, 255));
+        let (red, alpha) = row2[5];
+        assert_eq!(red, alpha);
+        assert!(alpha > 64 && alpha < 192, "{}", alpha);
+    }
+
+    #[test]
+    fn render_at_missing_model() {
+        let graphic = square(vec![(0.0, 0, Animation::Done)]);
+        let mut raster = Raster::<Rgba8p>::with_clear(8, 8);
+        assert!(render_at(&mut raster, &graphic, 1, 0.0, ()).is_none());
+        assert!(raster.pixels().iter().all(|p| u8::from(p.four()) == 0));
+    }
+
+    #[test]
+    fn render_clip_merges_frames() {
+        let graphic = square(vec![
+            (0.0, 100, Animation::Jump),
+            (4.0, 100, Animation::Linear),
+            (0.0, 0, Animation::Done),
+        ]);
+        let clip = render_clip(&graphic, 0, 20.0).unwrap();
+        assert_eq!(clip.loops, 1);
+        // The jumping frame is held, then the square slides every 50 ms.
+        let delays: Vec<u32> = clip.frames.iter().map(|(_, d)| *d).collect();
+        assert_eq!(delays, vec![100, 50, 50]);
+        assert_eq!(clip.frames[0].0.width(), 8);
+
+        assert!(render_clip(&graphic, 1, 20.0).is_none());
+        assert!(render_clip(&graphic, 0, 0.0).is_none());
+    }
+
+    #[test]
+    fn render_clip_loops() {
+        let mut graphic = square(vec![
+            (0.0, 100, Animation::Linear),
+            (4.0, 0, Animation::Done),
+        ]);
+        let playback = &mut graphic.models[0].playback;
+        playback.loops = 4;
+        playback.ping_pong = true;
+        // Ping-pong is rendered there and back, and played half as often.
+        let clip = render_clip(&graphic, 0, 10.0).unwrap();
+        assert_eq!(clip.loops, 2);
+        let total: u32 = clip.frames.iter().map(|(_, d)| *d).sum();
+        assert_eq!(total, 200);
+
+        // Looping forever keeps the start delay in every loop.
+        let playback = &mut graphic.models[0].playback;
+        playback.loops = 0;
+        playback.start_delay = 50;
+        let clip = render_clip(&graphic, 0, 10.0).unwrap();
+        assert_eq!(clip.loops, 0);
+        let total: u32 = clip.frames.iter().map(|(_, d)| *d).sum();
+        assert_eq!(total, 250);
+    }
+
+    #[test]
+    fn render_fill_rule() {
+        // A square with a smaller square inside it, wound the same way.
+        let mut graphic = square(vec![(0.0, 0, Animation::Done)]);
+        graphic.vertex_list = vec![
+            0.0, 0.0, 8.0, 0.0, 8.0, 8.0, 0.0, 8.0, //
+            2.0, 2.0, 6.0, 2.0, 6.0, 6.0, 2.0, 6.0,
+        ];
+        graphic.group[0].extend(vec![
+            PathOp::Move(4),
+            PathOp::Line(5),
+            PathOp::Line(6),
+            PathOp::Line(7),
+            PathOp::Close(),
+        ]);
+        let hole = |graphic: &Graphic| {
+            let mut raster = Raster::<Rgba8p>::with_clear(8, 8);
+            render_at(&mut raster, graphic, 0, 0.0, ()).unwrap();
+            (
+                u8::from(raster.pixel(4, 4).four()),
+                u8::from(raster.pixel(1, 4).four()),
+            )
+        };
+        assert_eq!(hole(&graphic), (255, 255));
+        graphic.models[0].groups[0]
+            .1
+            .push(GroupProperty::FillRule(crate::FillRule::EvenOdd));
+        assert_eq!(hole(&graphic), (0, 255));
+    }
+
+    /// Stroke a path through `points` 2 pixels wide, and get the alpha of
+    /// the pixels.
+    fn stroke(
+        points: &[(f32, f32)],
+        props: Vec<GroupProperty>,
+    ) -> Raster<Rgba8p> {
+        let mut graphic = square(vec![(0.0, 0, Animation::Done)]);
+        graphic.vertex_list =
+            points.iter().flat_map(|(x, y)| vec![*x, *y]).collect();
+        graphic.group[0] = (0..points.len() as u32)
+            .map(|i| {
+                if i == 0 {
+                    PathOp::Move(i)
+                } else {
+                    PathOp::Line(i)
+                }
+            })
+            .collect();
+        let mut props = props;
+        props.push(GroupProperty::StrokeColorRgba([255, 255, 255, 255]));
+        props.push(GroupProperty::StrokeWidth(2.0));
+        graphic.models[0].groups[0].1 = props;
+        let mut raster = Raster::<Rgba8p>::with_clear(8, 8);
+        render_at(&mut raster, &graphic, 0, 0.0, ()).unwrap();
+        raster
+    }
+
+    /// Get the alpha of a pixel.
+    fn alpha(raster: &Raster<Rgba8p>, x: i32, y: i32) -> u8 {
+        u8::from(raster.pixel(x, y).four())
+    }
+
+    #[test]
+    fn render_line_caps() {
+        let line = [(2.0, 4.0), (6.0, 4.0)];
+        let cap = |cap| stroke(&line, vec![GroupProperty::LineCap(cap)]);
+
+        let butt = cap(crate::LineCap::Butt);
+        assert_eq!(alpha(&butt, 2, 4), 255);
+        assert_eq!(alpha(&butt, 5, 3), 255);
+        assert_eq!(alpha(&butt, 1, 4), 0);
+        assert_eq!(alpha(&butt, 6, 4), 0);
+
+        // Square caps reach half the width past each end, and no further.
+        let square = cap(crate::LineCap::Square);
+        for y in 3..5 {
+            assert_eq!(alpha(&square, 1, y), 255);
+            assert_eq!(alpha(&square, 6, y), 255);
+            assert_eq!(alpha(&square, 0, y), 0);
+            assert_eq!(alpha(&square, 7, y), 0);
+        }
+        assert_eq!(alpha(&square, 6, 2), 0);
+
+        // Round caps cover the corners of those pixels only in part.
+        let round = cap(crate::LineCap::Round);
+        let corner = alpha(&round, 6, 4);
+        assert!(corner > 0 && corner < 255, "{}", corner);
+        assert_eq!(alpha(&round, 7, 4), 0);
+    }
+
+    #[test]
+    fn render_line_joins() {
+        let corner = [(1.0, 6.0), (6.0, 6.0), (6.0, 1.0)];
+        let join = |join, limit| {
+            let props = vec![
+                GroupProperty::JoinStyle(join),
+                GroupProperty::MiterLimit(limit),
+            ];
+            alpha(&stroke(&corner, props), 6, 6)
+        };
+        // The outside corner is the pixel past both segments.
+        assert_eq!(join(crate::JoinStyle::Miter, 4.0), 255);
+        let bevel = join(crate::JoinStyle::Bevel, 4.0);
+        assert!(bevel > 96 && bevel < 160, "{}", bevel);
+        let round = join(crate::JoinStyle::Round, 4.0);
+        assert!(round >= bevel && round < 255, "{}", round);
+        // Miters longer than the limit are beveled.
+        assert_eq!(join(crate::JoinStyle::Miter, 1.0), bevel);
+    }
+
+    #[test]
+    fn render_dashes() {
+        let dash = GroupProperty::Dash {
+            pattern: vec![2.0, 2.0],
+            offset: 1.0,
+        };
+        let raster = stroke(&[(0.0, 4.0), (8.0, 4.0)], vec![dash]);
+        let row: Vec<u8> = (0..8).map(|x| alpha(&raster, x, 4)).collect();
+        assert_eq!(row, vec![255, 0, 0, 255, 255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn render_fill_gradient() {
+        let mut graphic = square(vec![(0.0, 0, Animation::Done)]);
+        graphic.gradients = vec![crate::Gradient {
+            shape: crate::GradientShape::Linear {
+                start: [0.0, 0.0],
+                end: [4.0, 0.0],
+            },
+            stops: vec![(0.0, [255, 0, 0, 255]), (1.0, [0, 0, 255, 255])],
+            spread: crate::Spread::Pad,
+            transform: crate::Affine2::IDENTITY,
+        }];
+        graphic.models[0].groups[0].1 = vec![GroupProperty::FillGradient(0)];
+        let mut raster = Raster::<Rgba8p>::with_clear(8, 8);
+        render_at(&mut raster, &graphic, 0, 0.0, ()).unwrap();
+        let rgb = |x| {
+            let p = raster.pixel(x, 2);
+            (u8::from(p.one()), u8::from(p.three()), u8::from(p.four()))
+        };
+        let (r0, b0, a0) = rgb(0);
+        let (r3, b3, a3) = rgb(3);
+        assert_eq!((a0, a3), (255, 255));
+        assert!(r0 > r3 && b0 < b3, "{:?} {:?}", rgb(0), rgb(3));
+        assert_eq!(rgb(5), (0, 0, 0));
+    }
+
+    #[test]
+    fn render_vertex_colors() {
+        let mut graphic = square(vec![(0.0, 0, Animation::Done)]);
+        // Red on the left, and blue on the right.
+        graphic.attributes = vec![Attribute::Rgb];
+        graphic.vertex_list = vec![
+            0.0, 0.0, 1.0, 0.0, 0.0, //
+            4.0, 0.0, 0.0, 0.0, 1.0, //
+            4.0, 4.0, 0.0, 0.0, 1.0, //
+            0.0, 4.0, 1.0, 0.0, 0.0, //
+        ];
+        let mut raster = Raster::<Rgba8p>::with_clear(8, 8);
+        render_at(&mut raster, &graphic, 0, 0.0, ()).unwrap();
+        let rgba = |x, y| {
+            let p = raster.pixel(x, y);
+            let b = u8::from(p.three());
+            (u8::from(p.one()), b, u8::from(p.four()))
+        };
+        let (r0, b0, a0) = rgba(0, 1);
+        let (r3, b3, a3) = rgba(3, 1);
+        assert_eq!((a0, a3), (255, 255));
+        assert!(r0 > b0 && r3 < b3, "{:?} {:?}", rgba(0, 1), rgba(3, 1));
+        // Blended the same along each column, across both triangles.
+        assert_eq!(rgba(2, 0), rgba(2, 3));
+        assert_eq!(rgba(5, 1), (0, 0, 0));
+    }
+
+    /// A 2 × 2 bitmap: red and green on top, and blue and white below.
+    fn quad() -> Bitmap {
+        Bitmap {
+            width: 2,
+            height: 2,
+            srgba: vec![
+                255, 0, 0, 255, 0, 255, 0, 255, //
+                0, 0, 255, 255, 255, 255, 255, 255, //
+            ],
         }
     }
+
+    /// Render the first frame, and get the colors of the square's corners.
+    fn corners(graphic: &Graphic) -> [[u8; 4]; 4] {
+        let mut raster = Raster::<Rgba8p>::with_clear(8, 8);
+        render_at(&mut raster, graphic, 0, 0.0, ()).unwrap();
+        let rgba = |x, y| {
+            let p: Rgba8p = raster.pixel(x, y);
+            [p.one(), p.two(), p.three(), p.four()].map(u8::from)
+        };
+        [rgba(0, 0), rgba(3, 0), rgba(0, 3), rgba(3, 3)]
+    }
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+    #[test]
+    fn render_bitmap_tiles() {
+        let mut graphic = square(vec![(0.0, 0, Animation::Done)]);
+        graphic.bitmaps = vec![BitmapSource::Embedded(quad())];
+        graphic.models[0].groups[0].1 = vec![
+            GroupProperty::BitmapPattern(0),
+            GroupProperty::Sampling(crate::Sampling::Nearest),
+        ];
+        // Repeated every 2 pixels.
+        assert_eq!(corners(&graphic), [RED, GREEN, BLUE, WHITE]);
+
+        // Twice as large, and clamped past the edges.
+        graphic.models[0].groups[0].1.extend(vec![
+            GroupProperty::PatternTransform([2.0, 0.0, 0.0, 2.0, 0.0, 0.0]),
+            GroupProperty::Wrap(crate::Wrap::Clamp),
+        ]);
+        assert_eq!(corners(&graphic), [RED, GREEN, BLUE, WHITE]);
+        graphic.models[0].groups[0].1[2] =
+            GroupProperty::PatternTransform([1.0, 0.0, 0.0, 1.0, 3.0, 3.0]);
+        assert_eq!(corners(&graphic), [RED, RED, RED, RED]);
+    }
+
+    #[test]
+    fn render_bitmap_texture_coordinates() {
+        let mut graphic = square(vec![(0.0, 0, Animation::Done)]);
+        graphic.bitmaps = vec![BitmapSource::Embedded(quad())];
+        graphic.models[0].groups[0].1 = vec![
+            GroupProperty::BitmapPattern(0),
+            GroupProperty::Sampling(crate::Sampling::Nearest),
+        ];
+        // Stretched across the square, and flipped upside down.
+        graphic.attributes = vec![Attribute::UvTextureCoordinates];
+        graphic.vertex_list = vec![
+            0.0, 0.0, 0.0, 1.0, //
+            4.0, 0.0, 1.0, 1.0, //
+            4.0, 4.0, 1.0, 0.0, //
+            0.0, 4.0, 0.0, 0.0, //
+        ];
+        assert_eq!(corners(&graphic), [BLUE, WHITE, RED, GREEN]);
+    }
+
+    #[test]
+    fn render_bitmap_references() {
+        let mut graphic = square(vec![(0.0, 0, Animation::Done)]);
+        graphic.bitmaps =
+            vec![BitmapSource::External("quad.bitmap".to_string())];
+        graphic.models[0].groups[0].1.extend(vec![
+            GroupProperty::BitmapPattern(0),
+            GroupProperty::Sampling(crate::Sampling::Nearest),
+        ]);
+        // Without the bitmap, it is filled as if it had no pattern.
+        assert_eq!(corners(&graphic), [RED, RED, RED, RED]);
+
+        let mut resolver = crate::MemoryResolver::new();
+        let mut raster = Raster::<Rgba8p>::with_clear(8, 8);
+        render_with(&mut raster, &graphic, 0, 0.0, (), &resolver).unwrap();
+        assert_eq!(alpha(&raster, 3, 3), 255);
+        resolver.insert_bitmap("quad.bitmap", &quad());
+        render_with(&mut raster, &graphic, 0, 0.0, (), &resolver).unwrap();
+        let p = raster.pixel(3, 3);
+        let white = [p.one(), p.two(), p.three(), p.four()].map(u8::from);
+        assert_eq!(white, WHITE);
+        assert!(
+            render_with(&mut raster, &graphic, 1, 0.0, (), &resolver).is_none()
+        );
+    }
+
+    /// The square, filled with tiles of a 2 × 2 model of a green pixel in
+    /// its top left corner.
+    fn tiled() -> Graphic {
+        let mut graphic = square(vec![(0.0, 0, Animation::Done)]);
+        graphic
+            .vertex_list
+            .extend(&[0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0]);
+        graphic.group.push(vec![
+            PathOp::Move(4),
+            PathOp::Line(5),
+            PathOp::Line(6),
+            PathOp::Line(7),
+            PathOp::Close(),
+        ]);
+        graphic.models[0].groups[0].1 = vec![
+            GroupProperty::GroupPattern(1),
+            GroupProperty::Sampling(crate::Sampling::Nearest),
+        ];
+        graphic.models.push(Model {
+            width: 2.0,
+            height: 2.0,
+            groups: vec![(
+                1,
+                vec![GroupProperty::FillColorRgba([0, 255, 0, 255])],
+            )],
+            frames: vec![Frame {
+                transforms: vec![],
+                properties: vec![],
+                morph: Morph::None,
+                delay: 0,
+                animation: Animation::Done,
+            }],
+            playback: Playback::default(),
+        });
+        graphic
+    }
+
+    #[test]
+    fn render_group_pattern() {
+        let mut graphic = tiled();
+        const CLEAR: [u8; 4] = [0, 0, 0, 0];
+        let row = |graphic: &Graphic| {
+            let mut raster = Raster::<Rgba8p>::with_clear(8, 8);
+            render_at(&mut raster, graphic, 0, 0.0, ()).unwrap();
+            (0..5)
+                .map(|x| {
+                    let p: Rgba8p = raster.pixel(x, 0);
+                    [p.one(), p.two(), p.three(), p.four()].map(u8::from)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(row(&graphic), vec![GREEN, CLEAR, GREEN, CLEAR, CLEAR]);
+
+        // Spaced 2 apart, and moved 1 across.
+        graphic.models[0].groups[0].1.extend(vec![
+            GroupProperty::TileSpacing(2.0, 0.0),
+            GroupProperty::PatternTransform([1.0, 0.0, 0.0, 1.0, 1.0, 0.0]),
+        ]);
+        assert_eq!(row(&graphic), vec![CLEAR, GREEN, CLEAR, CLEAR, CLEAR]);
+
+        // A model can't be tiled within itself, so it is filled with color.
+        graphic.models[0].groups[0].1 = vec![
+            GroupProperty::FillColorRgba([255, 0, 0, 255]),
+            GroupProperty::GroupPattern(0),
+        ];
+        assert_eq!(row(&graphic), vec![RED, RED, RED, RED, CLEAR]);
+    }
+
+    #[test]
+    fn render_clip_animated_tiles() {
+        // The square is still, but its tiles move.
+        let mut graphic = tiled();
+        graphic.models[0].frames = vec![
+            Frame {
+                transforms: vec![],
+                properties: vec![],
+                morph: Morph::None,
+                delay: 100,
+                animation: Animation::Jump,
+            };
+            2
+        ];
+        graphic.models[0].frames[1].animation = Animation::Done;
+        let mut frames = graphic.models[1].frames[0].clone();
+        frames.delay = 100;
+        frames.animation = Animation::Jump;
+        let mut moved = frames.clone();
+        moved.transforms = vec![vec![TransformOp::Translate(1.0, 0.0, 0.0)]];
+        moved.animation = Animation::Done;
+        graphic.models[1].frames = vec![frames, moved];
+        let clip = render_clip(&graphic, 0, 10.0).unwrap();
+        assert_eq!(clip.frames.len(), 2);
+        assert!(is_animated(&graphic, 0, &mut vec![]));
+
+        // Tiles of still models are kept, even where models tile each other.
+        graphic.models[0].frames.truncate(1);
+        graphic.models[1].frames.truncate(1);
+        graphic.models[1].groups[0]
+            .1
+            .push(GroupProperty::GroupPattern(0));
+        assert!(!is_animated(&graphic, 0, &mut vec![]));
+    }
+
+    #[test]
+    fn render_clips_to_model() {
+        // A model 5 pixels wide, with a square wider than it.
+        let mut graphic = square(vec![(0.0, 0, Animation::Done)]);
+        graphic.vertex_list = vec![0.0, 0.0, 8.0, 0.0, 8.0, 8.0, 0.0, 8.0];
+        graphic.models[0].width = 5.0;
+        let mut raster = Raster::<Rgba8p>::with_clear(8, 8);
+        render_at(&mut raster, &graphic, 0, 0.0, ()).unwrap();
+        assert_eq!(alpha(&raster, 4, 4), 255);
+        assert_eq!(alpha(&raster, 5, 4), 0);
+        assert_eq!(alpha(&raster, 7, 7), 0);
+    }
+
+    #[test]
+    fn render_scaled_stroke() {
+        // A line 2 units wide, rendered twice as large.
+        let mut graphic = square(vec![(0.0, 0, Animation::Done)]);
+        graphic.vertex_list = vec![0.0, 2.0, 4.0, 2.0];
+        graphic.group[0] = vec![PathOp::Move(0), PathOp::Line(1)];
+        graphic.models[0].groups[0].1 = vec![
+            GroupProperty::StrokeColorRgba([255, 0, 0, 255]),
+            GroupProperty::StrokeWidth(2.0),
+        ];
+        let mut raster = Raster::<Rgba8p>::with_clear(8, 8);
+        render_at(&mut raster, &graphic, 0, 0.0, (0.0, 0.0, 16.0, 16.0))
+            .unwrap();
+        let column: Vec<u8> = (0..8).map(|y| alpha(&raster, 4, y)).collect();
+        assert_eq!(column, vec![0, 0, 255, 255, 255, 255, 0, 0]);
+    }
+
+    #[test]
+    fn render_stroke_width_attribute() {
+        // A line from 2 units wide on the left to 6 on the right.
+        let mut graphic = square(vec![(0.0, 0, Animation::Done)]);
+        graphic.attributes = vec![Attribute::StrokeWidth];
+        graphic.vertex_list = vec![0.0, 4.0, 2.0, 8.0, 4.0, 6.0];
+        graphic.group[0] = vec![PathOp::Move(0), PathOp::Line(1)];
+        graphic.models[0].groups[0].1 = vec![
+            GroupProperty::StrokeColorRgba([255, 0, 0, 255]),
+            GroupProperty::StrokeWidth(1.0),
+        ];
+        let mut raster = Raster::<Rgba8p>::with_clear(8, 8);
+        render_at(&mut raster, &graphic, 0, 0.0, ()).unwrap();
+        let width = |x| (0..8).filter(|&y| alpha(&raster, x, y) >= 128).count();
+        assert_eq!((width(0), width(7)), (2, 6));
+    }
 }
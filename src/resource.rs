@@ -0,0 +1,393 @@
+use crate::{Bitmap, BitmapSource, Graphic};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+/// Decode resource data as a bitmap.  Data is either a PNG file (with the
+/// `png` feature), or a bitmap in the layout RVG files embed them with.
+fn decode_bitmap(data: &[u8]) -> Option<Bitmap> {
+    #[cfg(feature = "png")]
+    {
+        if data.starts_with(b"\x89PNG") {
+            return Bitmap::from_png(data);
+        }
+    }
+    let width = u16::from_le_bytes([*data.first()?, *data.get(1)?]);
+    let height = u16::from_le_bytes([*data.get(2)?, *data.get(3)?]);
+    let srgba = data.get(4..)?;
+    if srgba.len() != usize::from(width) * usize::from(height) * 4 {
+        return None;
+    }
+    Some(Bitmap {
+        width,
+        height,
+        srgba: srgba.to_vec(),
+    })
+}
+
+/// Supplies the data for resources referenced, rather than embedded, by a
+/// graphic.
+pub trait ResourceResolver {
+    /// Get the raw bytes of the resource at `uri`.
+    fn resolve(&self, uri: &str) -> Option<Vec<u8>>;
+
+    /// Load the bitmap at `uri`.
+    fn resolve_bitmap(&self, uri: &str) -> Option<Bitmap> {
+        decode_bitmap(&self.resolve(uri)?)
+    }
+
+    /// Load the RVG graphic at `uri`.  References within it are resolved
+    /// with this resolver.
+    fn resolve_graphic(&self, uri: &str) -> Option<Graphic>
+    where
+        Self: Sized,
+    {
+        Graphic::load_with(self.resolve(uri)?.as_slice(), self)
+    }
+}
+
+/// Resolves resources from the filesystem.  Relative paths and `file://`
+/// URIs are looked up relative to a root directory, and can't leave it:
+/// absolute paths and `..` aren't resolved.
+pub struct FsResolver {
+    root: PathBuf,
+}
+
+impl FsResolver {
+    /// Create a resolver for paths relative to `root`.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        FsResolver { root: root.into() }
+    }
+}
+
+impl ResourceResolver for FsResolver {
+    fn resolve(&self, uri: &str) -> Option<Vec<u8>> {
+        let path = if let Some(path) = uri.strip_prefix("file://") {
+            path
+        } else if uri.contains("://") {
+            return None;
+        } else {
+            uri
+        };
+        let path = Path::new(path);
+        let inside = |component| {
+            matches!(component, Component::Normal(_) | Component::CurDir)
+        };
+        if !path.components().all(inside) {
+            return None;
+        }
+        std::fs::read(self.root.join(path)).ok()
+    }
+}
+
+/// Resolves resources from a map of URIs to data held in memory.
+#[derive(Default)]
+pub struct MemoryResolver {
+    resources: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryResolver {
+    /// Create an empty resolver.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the raw bytes of a resource.
+    pub fn insert<U: Into<String>>(&mut self, uri: U, data: Vec<u8>) {
+        self.resources.insert(uri.into(), data);
+    }
+
+    /// Add a bitmap resource.
+    pub fn insert_bitmap<U: Into<String>>(&mut self, uri: U, bitmap: &Bitmap) {
+        let mut data = Vec::with_capacity(bitmap.srgba.len() + 4);
+        data.extend(&bitmap.width.to_le_bytes());
+        data.extend(&bitmap.height.to_le_bytes());
+        data.extend(&bitmap.srgba);
+        self.insert(uri, data);
+    }
+}
+
+impl ResourceResolver for MemoryResolver {
+    fn resolve(&self, uri: &str) -> Option<Vec<u8>> {
+        self.resources.get(uri).cloned()
+    }
+}
+
+/// Deepest that graphics referenced by graphics are loaded, to stop at
+/// references that form a cycle.
+const MAX_DEPTH: u32 = 8;
+
+/// Render the first model of a graphic as a bitmap, at the model's size.
+#[cfg(feature = "render")]
+fn render_bitmap(graphic: &Graphic) -> Option<Bitmap> {
+    use pix::rgb::Rgba8p;
+    use pix::Raster;
+
+    let model = graphic.models.first()?;
+    let fits = |length: f32| (0.0..=f32::from(u16::MAX)).contains(&length);
+    if !(fits(model.width) && fits(model.height)) {
+        return None;
+    }
+    let (width, height) = (model.width as u32, model.height as u32);
+    let mut raster = Raster::<Rgba8p>::with_clear(width, height);
+    crate::render_at(&mut raster, graphic, 0, 0.0, ())?;
+    Bitmap::from_raster(&Raster::with_raster(&raster))
+}
+
+/// Graphics can't be rendered as bitmaps without the `render` feature.
+#[cfg(not(feature = "render"))]
+fn render_bitmap(_graphic: &Graphic) -> Option<Bitmap> {
+    None
+}
+
+impl BitmapSource {
+    /// Load the bitmap with a resolver, rendering it if it is a graphic.
+    /// Embedded bitmaps are copied.
+    pub fn resolve<S>(&self, resolver: &S) -> Option<Bitmap>
+    where
+        S: ResourceResolver + ?Sized,
+    {
+        self.resolve_nested(resolver, 0)
+    }
+
+    /// Load the bitmap, within `depth` graphics referenced by graphics.
+    fn resolve_nested<S>(&self, resolver: &S, depth: u32) -> Option<Bitmap>
+    where
+        S: ResourceResolver + ?Sized,
+    {
+        match self {
+            BitmapSource::Embedded(bitmap) => Some(bitmap.clone()),
+            BitmapSource::External(uri) => resolver.resolve_bitmap(uri),
+            BitmapSource::Graphic(uri) if depth < MAX_DEPTH => {
+                let data = resolver.resolve(uri)?;
+                let mut graphic = Graphic::load(data.as_slice())?;
+                // Bitmaps of the graphic that can't be loaded aren't drawn.
+                graphic.resolve_nested(resolver, depth + 1);
+                render_bitmap(&graphic)
+            }
+            BitmapSource::Graphic(_) => None,
+        }
+    }
+}
+
+impl Graphic {
+    /// Load a graphic, embedding every external bitmap and graphic it
+    /// references.
+    pub fn load_with<R, S>(reader: R, resolver: &S) -> Option<Graphic>
+    where
+        R: Read,
+        S: ResourceResolver + ?Sized,
+    {
+        let mut graphic = Graphic::load(reader)?;
+        graphic.resolve(resolver)?;
+        Some(graphic)
+    }
+
+    /// Embed every external bitmap, and render every external graphic into
+    /// a bitmap.  Returns `None` if any of them could not be resolved,
+    /// leaving those as references.
+    pub fn resolve<S>(&mut self, resolver: &S) -> Option<()>
+    where
+        S: ResourceResolver + ?Sized,
+    {
+        self.resolve_nested(resolver, 0)
+    }
+
+    /// Embed every bitmap, within `depth` graphics referenced by graphics.
+    fn resolve_nested<S>(&mut self, resolver: &S, depth: u32) -> Option<()>
+    where
+        S: ResourceResolver + ?Sized,
+    {
+        let mut resolved = true;
+        for source in self.bitmaps.iter_mut() {
+            if source.embedded().is_none() {
+                match source.resolve_nested(resolver, depth) {
+                    Some(bitmap) => *source = BitmapSource::Embedded(bitmap),
+                    None => resolved = false,
+                }
+            }
+        }
+        if resolved {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Get a bitmap by index, resolving it at the time of the call if it is
+    /// a reference.
+    pub fn bitmap<S>(
+        &self,
+        index: usize,
+        resolver: &S,
+    ) -> Option<Cow<'_, Bitmap>>
+    where
+        S: ResourceResolver + ?Sized,
+    {
+        match self.bitmaps.get(index)? {
+            BitmapSource::Embedded(bitmap) => Some(Cow::Borrowed(bitmap)),
+            source => source.resolve(resolver).map(Cow::Owned),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// A directory under the system's temporary directory, removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "rvg-{}-{}",
+                name,
+                std::process::id()
+            ));
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn fs_resolver_inside_root() {
+        let dir = TempDir::new("fs-inside");
+        fs::create_dir_all(dir.0.join("sub")).unwrap();
+        fs::write(dir.0.join("a.bin"), b"a").unwrap();
+        fs::write(dir.0.join("sub").join("b.bin"), b"b").unwrap();
+        let resolver = FsResolver::new(&dir.0);
+        assert_eq!(resolver.resolve("a.bin").unwrap(), b"a");
+        assert_eq!(resolver.resolve("./a.bin").unwrap(), b"a");
+        assert_eq!(resolver.resolve("sub/b.bin").unwrap(), b"b");
+        assert_eq!(resolver.resolve("file://sub/b.bin").unwrap(), b"b");
+        assert!(resolver.resolve("missing.bin").is_none());
+        assert!(resolver.resolve("https://example.com/a.bin").is_none());
+    }
+
+    #[test]
+    fn fs_resolver_escapes_root() {
+        let dir = TempDir::new("fs-escape");
+        let root = dir.0.join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        let outside = dir.0.join("outside.bin");
+        fs::write(&outside, b"secret").unwrap();
+        let resolver = FsResolver::new(&root);
+        let absolute = outside.to_str().unwrap();
+        assert!(resolver.resolve(absolute).is_none());
+        assert!(resolver.resolve(&format!("file://{}", absolute)).is_none());
+        assert!(resolver.resolve("../outside.bin").is_none());
+        assert!(resolver.resolve("sub/../../outside.bin").is_none());
+        assert!(resolver.resolve("file://../outside.bin").is_none());
+    }
+
+    #[test]
+    fn resolve_bitmap_reference() {
+        let bitmap = Bitmap {
+            width: 1,
+            height: 2,
+            srgba: vec![1, 2, 3, 4, 5, 6, 7, 8],
+        };
+        let mut resolver = MemoryResolver::new();
+        resolver.insert_bitmap("a.bitmap", &bitmap);
+        resolver.insert("bad.bitmap", vec![1, 0, 1, 0, 9]);
+        let mut graphic = Graphic {
+            attributes: vec![],
+            vertex_list: vec![],
+            group: vec![],
+            gradients: vec![],
+            models: vec![],
+            bitmaps: vec![
+                BitmapSource::External("a.bitmap".to_string()),
+                BitmapSource::External("bad.bitmap".to_string()),
+            ],
+        };
+        let copy = graphic.bitmap(0, &resolver).unwrap();
+        assert_eq!((copy.width, copy.height), (1, 2));
+        assert_eq!(copy.srgba, bitmap.srgba);
+        assert!(graphic.bitmap(1, &resolver).is_none());
+        assert!(graphic.bitmap(2, &resolver).is_none());
+
+        // The bitmap that can't be resolved is left as a reference.
+        assert!(graphic.resolve(&resolver).is_none());
+        assert_eq!(graphic.bitmaps[0].embedded().unwrap().srgba, bitmap.srgba);
+        assert!(graphic.bitmaps[1].embedded().is_none());
+    }
+
+    #[cfg(all(feature = "render", feature = "zstd"))]
+    #[test]
+    fn resolve_graphic_reference() {
+        use crate::{
+            Animation, Frame, GroupProperty, Model, Morph, PathOp, Playback,
+        };
+
+        let tile = Graphic {
+            attributes: vec![],
+            vertex_list: vec![0.0, 0.0, 4.0, 0.0, 4.0, 2.0, 0.0, 2.0],
+            group: vec![vec![
+                PathOp::Move(0),
+                PathOp::Line(1),
+                PathOp::Line(2),
+                PathOp::Line(3),
+                PathOp::Close(),
+            ]],
+            gradients: vec![],
+            models: vec![Model {
+                width: 4.0,
+                height: 2.0,
+                groups: vec![(
+                    0,
+                    vec![GroupProperty::FillColorRgba([255, 0, 0, 255])],
+                )],
+                frames: vec![Frame {
+                    transforms: vec![],
+                    properties: vec![],
+                    morph: Morph::None,
+                    delay: 0,
+                    animation: Animation::Done,
+                }],
+                playback: Playback::default(),
+            }],
+            bitmaps: vec![],
+        };
+        let mut data = Vec::new();
+        tile.save(&mut data).unwrap();
+        let mut resolver = MemoryResolver::new();
+        resolver.insert("tile.rvg", data);
+
+        let source = BitmapSource::Graphic("tile.rvg".to_string());
+        let bitmap = source.resolve(&resolver).unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (4, 2));
+        for pixel in bitmap.srgba.chunks(4) {
+            assert_eq!(pixel, [255, 0, 0, 255]);
+        }
+        assert!(BitmapSource::Graphic("missing.rvg".to_string())
+            .resolve(&resolver)
+            .is_none());
+
+        // A graphic filled with a pattern of itself stops at a depth, where
+        // it is filled with its color.
+        let mut cycle = tile;
+        cycle.bitmaps = vec![BitmapSource::Graphic("cycle.rvg".to_string())];
+        cycle.models[0].groups[0].1.extend(vec![
+            GroupProperty::BitmapPattern(0),
+            GroupProperty::Sampling(crate::Sampling::Nearest),
+        ]);
+        let mut data = Vec::new();
+        cycle.save(&mut data).unwrap();
+        resolver.insert("cycle.rvg", data);
+        let bitmap = cycle.bitmap(0, &resolver).unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (4, 2));
+        for pixel in bitmap.srgba.chunks(4) {
+            assert_eq!(pixel, [255, 0, 0, 255]);
+        }
+    }
+}
@@ -1,12 +1,17 @@
+use crate::Affine2;
+use pix::{rgb::SRgba8, Raster};
 use ruzstd::streaming_decoder::StreamingDecoder;
+use std::convert::TryInto;
 use std::io::prelude::*;
 
 #[cfg(feature = "zstd")]
 use zstd::stream::Encoder;
 
-const FORMAT_HEADER: [u8; 4] = [b'r', b'V', b'g', b'\x00'];
+const FORMAT_HEADER: [u8; 3] = [b'r', b'V', b'g'];
+const FORMAT_VERSION: u8 = 5;
 
 /// Pixel data
+#[derive(Clone)]
 pub struct Bitmap {
     /// Width of the image
     pub width: u16,
@@ -16,8 +21,47 @@ pub struct Bitmap {
     pub srgba: Vec<u8>,
 }
 
-///
-#[derive(PartialEq)]
+impl Bitmap {
+    /// Copy the pixels of a raster into a new bitmap.  Returns `None` if the
+    /// raster is too large to be stored in an RVG file.
+    pub fn from_raster(raster: &Raster<SRgba8>) -> Option<Bitmap> {
+        Some(Bitmap {
+            width: raster.width().try_into().ok()?,
+            height: raster.height().try_into().ok()?,
+            srgba: raster.as_u8_slice().to_vec(),
+        })
+    }
+
+    /// Copy the pixels of this bitmap into a new raster.  Returns `None` if
+    /// `srgba` doesn't hold exactly `width * height` pixels.
+    pub fn to_raster(&self) -> Option<Raster<SRgba8>> {
+        let len = usize::from(self.width) * usize::from(self.height) * 4;
+        if self.srgba.len() != len {
+            return None;
+        }
+        Some(Raster::with_u8_buffer(
+            self.width.into(),
+            self.height.into(),
+            self.srgba.as_slice(),
+        ))
+    }
+}
+
+/// Where the jumps of an `Animation::Steps` happen, like CSS `steps()`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StepPosition {
+    /// First jump happens at the start (`jump-start`, `start`)
+    JumpStart,
+    /// Last jump happens at the end (`jump-end`, `end`)
+    JumpEnd,
+    /// No jump at either the start or the end (`jump-none`)
+    JumpNone,
+    /// Jumps at both the start and the end (`jump-both`)
+    JumpBoth,
+}
+
+/// How a frame transitions into the next one.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Animation {
     /// Must be the last value.
     Done,
@@ -33,40 +77,428 @@ pub enum Animation {
     Fade,
     /// SrcOver each frame without clearing
     Layer,
+    /// CSS `cubic-bezier(x1, y1, x2, y2)` timing function
+    CubicBezier(f32, f32, f32, f32),
+    /// CSS `steps(n, position)` timing function
+    Steps(u32, StepPosition),
 }
 
-///
-pub enum Transform {
+/// A transform operation.  A group's list of operations is applied to its
+/// vertices in order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransformOp {
+    /// Translate by (x, y, z)
     Translate(f32, f32, f32),
+    /// Scale by (x, y, z)
     Scale(f32, f32, f32),
+    /// Rotate by an angle in radians about the axis (vx, vy, vz, rot)
     Rotate(f32, f32, f32, f32),
+    /// Skew by angles in radians along the X and Y axes (x, y)
+    Skew(f32, f32),
+    /// Apply a 2D affine matrix [a, b, c, d, e, f], like SVG's `matrix()`
+    Matrix([f32; 6]),
+    /// Move to a normalized distance (0 to 1) along the arc length of a path
+    /// in `Graphic::group`, optionally rotating to its tangent.  The path is
+    /// followed where the first group of the model drawing it puts it, in
+    /// the space of the moving group's parent.
+    /// (path, distance, auto_rotate)
+    MotionPath(u32, f32, bool),
 }
 
-///
+/// An animation keyframe.
+#[derive(Clone, Debug, PartialEq)]
 pub struct Frame {
-    pub transforms: Vec<Transform>,
+    /// A list of transform operations for each of the model's groups, in
+    /// the same order as `Model::groups`.  Missing lists are empty.
+    pub transforms: Vec<Vec<TransformOp>>,
+    /// Overrides of the fill color, stroke color, stroke width and opacity
+    /// properties of each group, in the same order as `Model::groups`.
+    /// Other properties are ignored, and missing lists override nothing.
+    pub properties: Vec<Vec<GroupProperty>>,
+    /// Positions that vertices move to at this keyframe
+    pub morph: Morph,
+    /// Milliseconds until the next frame, 0 for nonlinear
     pub delay: u16,
+    /// How this frame transitions into the next one
     pub animation: Animation,
 }
 
-///
+/// Vertex positions of a keyframe, replacing the ones in
+/// `Graphic::vertex_list`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Morph {
+    /// Vertices stay where they are in the vertex list
+    #[default]
+    None,
+    /// Position (x, y) of every vertex, in vertex list order
+    Snapshot(Vec<[f32; 2]>),
+    /// Positions (x, y) of some vertices, by vertex index
+    Sparse(Vec<(u32, [f32; 2])>),
+}
+
+impl Morph {
+    /// Get the position a vertex moves to, if it moves.
+    pub fn position(&self, index: u32) -> Option<[f32; 2]> {
+        match self {
+            Morph::None => None,
+            Morph::Snapshot(positions) => {
+                positions.get(index as usize).cloned()
+            }
+            Morph::Sparse(positions) => positions
+                .iter()
+                .rev()
+                .find(|(i, _)| *i == index)
+                .map(|(_, position)| *position),
+        }
+    }
+
+    /// Get the indices of the vertices that move.
+    pub fn indices(&self) -> Vec<u32> {
+        match self {
+            Morph::None => Vec::new(),
+            Morph::Snapshot(positions) => (0..positions.len() as u32).collect(),
+            Morph::Sparse(positions) => {
+                positions.iter().map(|(i, _)| *i).collect()
+            }
+        }
+    }
+}
+
+/// A rule for which parts of a path are inside it, like SVG's `fill-rule`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FillRule {
+    /// Inside where the path winds around a point a non-zero number of times
+    #[default]
+    NonZero,
+    /// Inside where a ray from a point crosses the path an odd number of times
+    EvenOdd,
+}
+
+/// How the segments of a stroke are joined, like SVG's `stroke-linejoin`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum JoinStyle {
+    /// Extend the outer edges until they meet, up to the miter limit
+    #[default]
+    Miter,
+    /// Round off the corner
+    Round,
+    /// Cut off the corner
+    Bevel,
+}
+
+/// How the ends of unclosed subpaths are stroked, like SVG's
+/// `stroke-linecap`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LineCap {
+    /// End the stroke at the end point
+    #[default]
+    Butt,
+    /// Extend the stroke with a half circle
+    Round,
+    /// Extend the stroke by half its width
+    Square,
+}
+
+/// How a bitmap is sampled between the centers of its pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Sampling {
+    /// Use the nearest pixel
+    Nearest,
+    /// Blend the four nearest pixels
+    #[default]
+    Bilinear,
+}
+
+/// How a bitmap continues past its edges.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Wrap {
+    /// Repeat the bitmap
+    #[default]
+    Repeat,
+    /// Continue the pixels at the edges
+    Clamp,
+}
+
+/// A rendering property of a group.
+#[derive(Clone, Debug, PartialEq)]
 pub enum GroupProperty {
     FillColorRgba([u8; 4]),
     StrokeColorRgba([u8; 4]),
     StrokeWidth(f32),
-    JoinStyle(u8),
-    FillRule(u8),
+    /// How the segments of the stroke are joined
+    JoinStyle(JoinStyle),
+    /// Which parts of the path are inside it, and filled
+    FillRule(FillRule),
     GlyphID(u32),
+    /// Index in `Graphic::bitmaps` of the bitmap the fill is painted with,
+    /// instead of its color or gradient
     BitmapPattern(u32),
+    /// Index in `Graphic::models` of the model the fill is painted with
+    /// tiles of, instead of its color or gradient
     GroupPattern(u32),
+    /// Point that rotation, scale and skew transforms of the group act about
+    /// (x, y)
+    Pivot(f32, f32),
+    /// Opacity (0 to 1) that the alpha of the fill and stroke are multiplied by
+    Opacity(f32),
+    /// Index in `Model::groups` of the group whose transform this group's
+    /// transform is applied within
+    Parent(u32),
+    /// How the ends of unclosed subpaths are stroked
+    LineCap(LineCap),
+    /// Longest miter join, as a ratio of miter length to stroke width, before
+    /// it is beveled instead
+    MiterLimit(f32),
+    /// Lengths along the stroke that are alternately drawn and skipped, and
+    /// how far into them the stroke starts
+    Dash {
+        pattern: Vec<f32>,
+        offset: f32,
+    },
+    /// Index in `Graphic::gradients` of the gradient the fill is painted
+    /// with, instead of its color
+    FillGradient(u32),
+    /// Index in `Graphic::gradients` of the gradient the stroke is painted
+    /// with, instead of its color
+    StrokeGradient(u32),
+    /// Matrix [a, b, c, d, e, f] from the space of a pattern to the space of
+    /// the vertices
+    PatternTransform([f32; 6]),
+    /// How the bitmap of a `BitmapPattern`, or tiles of a `GroupPattern`, are
+    /// sampled
+    Sampling(Sampling),
+    /// How the bitmap of a `BitmapPattern` continues past its edges
+    Wrap(Wrap),
+    /// Size of each tile of a `GroupPattern` (width, height), in the space
+    /// of the pattern
+    TileSize(f32, f32),
+    /// Gap between the tiles of a `GroupPattern` (x, y), in the space of the
+    /// pattern
+    TileSpacing(f32, f32),
 }
 
-///
+/// A graphic made of styled groups, and optionally animated.
 pub struct Model {
     pub width: f32,
     pub height: f32,
     pub groups: Vec<(u32, Vec<GroupProperty>)>,
     pub frames: Vec<Frame>,
+    pub playback: Playback,
+}
+
+/// How a model's timeline plays back.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Playback {
+    /// Number of times to play the timeline, or 0 to loop forever
+    pub loops: u32,
+    /// Play every other loop backwards
+    pub ping_pong: bool,
+    /// Milliseconds to show the first frame before the timeline starts
+    pub start_delay: u32,
+    /// Milliseconds one loop of the timeline lasts, with frame delays scaled
+    /// to fit, or 0 to use the sum of frame delays
+    pub duration: u32,
+    /// Color space that animated colors are interpolated in
+    pub color_space: ColorSpace,
+}
+
+/// A color space for interpolating colors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSpace {
+    /// Interpolate the gamma-encoded sRGB values
+    Srgb,
+    /// Interpolate linear light intensities
+    Linear,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Playback {
+            loops: 1,
+            ping_pong: false,
+            start_delay: 0,
+            duration: 0,
+            color_space: ColorSpace::Srgb,
+        }
+    }
+}
+
+impl Model {
+    /// Get the last value of a kind of property of a group, by its index in
+    /// `groups`.
+    fn property<'a, T, F>(&'a self, group: usize, kind: F) -> Option<T>
+    where
+        F: Fn(&'a GroupProperty) -> Option<T>,
+    {
+        let (_, props) = self.groups.get(group)?;
+        props.iter().rev().find_map(kind)
+    }
+
+    /// Get the pivot point of a group, by its index in `groups`.  Groups
+    /// without a `Pivot` property pivot about the origin.
+    pub fn pivot(&self, group: usize) -> (f32, f32) {
+        self.property(group, |prop| match *prop {
+            GroupProperty::Pivot(x, y) => Some((x, y)),
+            _ => None,
+        })
+        .unwrap_or((0.0, 0.0))
+    }
+
+    /// Get the fill rule of a group, by its index in `groups`.  Groups
+    /// without a `FillRule` property use `FillRule::NonZero`.
+    pub fn fill_rule(&self, group: usize) -> FillRule {
+        self.property(group, |prop| match *prop {
+            GroupProperty::FillRule(rule) => Some(rule),
+            _ => None,
+        })
+        .unwrap_or_default()
+    }
+
+    /// Get the join style of a group's stroke, by its index in `groups`.
+    /// Groups without a `JoinStyle` property use `JoinStyle::Miter`.
+    pub fn join_style(&self, group: usize) -> JoinStyle {
+        self.property(group, |prop| match *prop {
+            GroupProperty::JoinStyle(style) => Some(style),
+            _ => None,
+        })
+        .unwrap_or_default()
+    }
+
+    /// Get the line cap of a group's stroke, by its index in `groups`.
+    /// Groups without a `LineCap` property use `LineCap::Butt`.
+    pub fn line_cap(&self, group: usize) -> LineCap {
+        self.property(group, |prop| match *prop {
+            GroupProperty::LineCap(cap) => Some(cap),
+            _ => None,
+        })
+        .unwrap_or_default()
+    }
+
+    /// Get the index in `Graphic::bitmaps` of the bitmap a group's fill is
+    /// painted with, by its index in `groups`.
+    pub fn bitmap_pattern(&self, group: usize) -> Option<u32> {
+        self.property(group, |prop| match *prop {
+            GroupProperty::BitmapPattern(bitmap) => Some(bitmap),
+            _ => None,
+        })
+    }
+
+    /// Get the index in `Graphic::models` of the model a group's fill is
+    /// painted with tiles of, by its index in `groups`.
+    pub fn group_pattern(&self, group: usize) -> Option<u32> {
+        self.property(group, |prop| match *prop {
+            GroupProperty::GroupPattern(model) => Some(model),
+            _ => None,
+        })
+    }
+
+    /// Get the size of the tiles of a group's pattern, by its index in
+    /// `groups`.  Groups without a `TileSize` property use the size of the
+    /// pattern's model.
+    pub fn tile_size(&self, group: usize) -> Option<(f32, f32)> {
+        self.property(group, |prop| match *prop {
+            GroupProperty::TileSize(width, height) => Some((width, height)),
+            _ => None,
+        })
+    }
+
+    /// Get the gap between the tiles of a group's pattern, by its index in
+    /// `groups`.  Groups without a `TileSpacing` property have no gap.
+    pub fn tile_spacing(&self, group: usize) -> (f32, f32) {
+        self.property(group, |prop| match *prop {
+            GroupProperty::TileSpacing(x, y) => Some((x, y)),
+            _ => None,
+        })
+        .unwrap_or((0.0, 0.0))
+    }
+
+    /// Get the transform from a group's pattern space to the space of its
+    /// vertices, by its index in `groups`.  Groups without a
+    /// `PatternTransform` property use the identity.
+    pub fn pattern_transform(&self, group: usize) -> Affine2 {
+        self.property(group, |prop| match *prop {
+            GroupProperty::PatternTransform(m) => Some(Affine2::new(m)),
+            _ => None,
+        })
+        .unwrap_or_default()
+    }
+
+    /// Get how a group's bitmap pattern or tiles are sampled, by its index
+    /// in `groups`.  Groups without a `Sampling` property use
+    /// `Sampling::Bilinear`.
+    pub fn sampling(&self, group: usize) -> Sampling {
+        self.property(group, |prop| match *prop {
+            GroupProperty::Sampling(sampling) => Some(sampling),
+            _ => None,
+        })
+        .unwrap_or_default()
+    }
+
+    /// Get how a group's bitmap pattern continues past its edges, by its
+    /// index in `groups`.  Groups without a `Wrap` property use
+    /// `Wrap::Repeat`.
+    pub fn wrap(&self, group: usize) -> Wrap {
+        self.property(group, |prop| match *prop {
+            GroupProperty::Wrap(wrap) => Some(wrap),
+            _ => None,
+        })
+        .unwrap_or_default()
+    }
+
+    /// Get the miter limit of a group's stroke, by its index in `groups`.
+    /// Groups without a `MiterLimit` property use 4, like SVG.
+    pub fn miter_limit(&self, group: usize) -> f32 {
+        self.property(group, |prop| match *prop {
+            GroupProperty::MiterLimit(limit) => Some(limit),
+            _ => None,
+        })
+        .unwrap_or(4.0)
+    }
+
+    /// Get the dash pattern and offset of a group's stroke, by its index in
+    /// `groups`.  Groups without a `Dash` property have solid strokes.
+    pub fn dash(&self, group: usize) -> Option<(&[f32], f32)> {
+        self.property(group, |prop| match *prop {
+            GroupProperty::Dash {
+                ref pattern,
+                offset,
+            } => Some((pattern.as_slice(), offset)),
+            _ => None,
+        })
+    }
+
+    /// Get the index in `Graphic::gradients` of the gradient a group's fill
+    /// is painted with, by its index in `groups`.
+    pub fn fill_gradient(&self, group: usize) -> Option<u32> {
+        self.property(group, |prop| match *prop {
+            GroupProperty::FillGradient(gradient) => Some(gradient),
+            _ => None,
+        })
+    }
+
+    /// Get the index in `Graphic::gradients` of the gradient a group's
+    /// stroke is painted with, by its index in `groups`.
+    pub fn stroke_gradient(&self, group: usize) -> Option<u32> {
+        self.property(group, |prop| match *prop {
+            GroupProperty::StrokeGradient(gradient) => Some(gradient),
+            _ => None,
+        })
+    }
+
+    /// Get the index in `groups` of a group's parent, by its index in
+    /// `groups`.  Groups without a `Parent` property, or with a parent that
+    /// doesn't exist, are at the root of the hierarchy.
+    pub fn parent(&self, group: usize) -> Option<usize> {
+        let parent = self.property(group, |prop| match *prop {
+            GroupProperty::Parent(parent) => Some(parent as usize),
+            _ => None,
+        })?;
+        if parent < self.groups.len() {
+            Some(parent)
+        } else {
+            None
+        }
+    }
 }
 
 /// An RVG PathOp
@@ -84,16 +516,17 @@ pub enum PathOp {
 }
 
 /// A vertex attribute.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Attribute {
     /// Z dimension (depth)
     Z,
     /// U,V Texture Coordinates
     UvTextureCoordinates,
-    /// Vertex Gradient
+    /// Vertex Gradient: sRGB color (0 to 1) that replaces the fill color
     Rgb,
-    /// Vertex Gradient
+    /// Vertex Gradient: sRGBA color (0 to 1) that replaces the fill color
     Rbga,
-    /// Vertex Gradient
+    /// Vertex Gradient: alpha (0 to 1) that replaces the fill color's alpha
     Alpha,
     /// Normal metadata
     Normal2D,
@@ -101,22 +534,133 @@ pub enum Attribute {
     Normal3D,
     /// Normal metadata
     Normal4D,
-    /// Stroke Width
+    /// Width of the stroke at the vertex, that replaces the stroke width
     StrokeWidth,
     /// User Defined metadata
     UserDefined(u8),
 }
 
+impl Attribute {
+    /// Get the number of values the attribute adds to each vertex.
+    pub fn dimensions(&self) -> usize {
+        match *self {
+            Attribute::Z | Attribute::Alpha | Attribute::StrokeWidth => 1,
+            Attribute::UvTextureCoordinates | Attribute::Normal2D => 2,
+            Attribute::Rgb | Attribute::Normal3D => 3,
+            Attribute::Rbga | Attribute::Normal4D => 4,
+            Attribute::UserDefined(n) => n.into(),
+        }
+    }
+}
+
+/// A bitmap, either embedded in the graphic or stored elsewhere.
+pub enum BitmapSource {
+    /// Pixel data stored in the graphic
+    Embedded(Bitmap),
+    /// URI or relative path of a bitmap loaded by a
+    /// [`ResourceResolver`](trait.ResourceResolver.html)
+    External(String),
+    /// URI or relative path of an RVG graphic, loaded by a
+    /// [`ResourceResolver`](trait.ResourceResolver.html) and rendered as the
+    /// bitmap
+    Graphic(String),
+}
+
+impl BitmapSource {
+    /// Get the pixel data, if it is embedded.
+    pub fn embedded(&self) -> Option<&Bitmap> {
+        match self {
+            BitmapSource::Embedded(bitmap) => Some(bitmap),
+            BitmapSource::External(_) | BitmapSource::Graphic(_) => None,
+        }
+    }
+}
+
+/// How a gradient continues before its first stop and after its last.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Spread {
+    /// Continue the color of the nearest stop
+    #[default]
+    Pad,
+    /// Repeat the gradient, reversed every other time
+    Reflect,
+    /// Repeat the gradient
+    Repeat,
+}
+
+/// The shape of a gradient, in its own coordinate space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GradientShape {
+    /// Along the line from `start` (offset 0) to `end` (offset 1)
+    Linear { start: [f32; 2], end: [f32; 2] },
+    /// Out from `focus` (offset 0) to the circle around `center` (offset 1)
+    Radial {
+        center: [f32; 2],
+        radius: f32,
+        focus: [f32; 2],
+    },
+    /// Around `center`, from `angle` (radians from the X axis towards the Y
+    /// axis, offset 0) all the way around (offset 1)
+    Conic { center: [f32; 2], angle: f32 },
+}
+
+/// A paint that blends between colors across a shape.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    pub shape: GradientShape,
+    /// Offsets (0 to 1) and sRGBA colors, in order of offset
+    pub stops: Vec<(f32, [u8; 4])>,
+    pub spread: Spread,
+    /// Transform from the gradient's space to the space of the vertices
+    pub transform: Affine2,
+}
+
 /// An RVG graphic that has been parsed, or will be parsed.
 pub struct Graphic {
     pub attributes: Vec<Attribute>,
     pub vertex_list: Vec<f32>,
     pub group: Vec<Vec<PathOp>>,
+    /// Gradient paints, referred to by `GroupProperty::FillGradient` and
+    /// `GroupProperty::StrokeGradient`
+    pub gradients: Vec<Gradient>,
     pub models: Vec<Model>,
-    pub bitmaps: Vec<Bitmap>,
+    pub bitmaps: Vec<BitmapSource>,
 }
 
 impl Graphic {
+    /// Get the number of values each vertex takes up in `vertex_list`: its
+    /// position, followed by its attributes.
+    pub fn stride(&self) -> usize {
+        2 + self
+            .attributes
+            .iter()
+            .map(Attribute::dimensions)
+            .sum::<usize>()
+    }
+
+    /// Get the position (x, y) of a vertex in `vertex_list`.
+    pub fn vertex(&self, index: u32) -> Option<[f32; 2]> {
+        let i = index as usize * self.stride();
+        Some([*self.vertex_list.get(i)?, *self.vertex_list.get(i + 1)?])
+    }
+
+    /// Get the values of the first attribute of a kind, for a vertex in
+    /// `vertex_list`.
+    pub fn attribute(
+        &self,
+        index: u32,
+        attribute: Attribute,
+    ) -> Option<&[f32]> {
+        let mut i = index as usize * self.stride() + 2;
+        for kind in &self.attributes {
+            if *kind == attribute {
+                return self.vertex_list.get(i..i + kind.dimensions());
+            }
+            i += kind.dimensions();
+        }
+        None
+    }
+
     pub fn load<R: Read>(mut reader: R) -> Option<Graphic> {
         let mut reader = StreamingDecoder::new(&mut reader).unwrap();
         let mut buf = vec![];
@@ -125,7 +669,7 @@ impl Graphic {
         let mut buf = buf.iter().cloned();
 
         // FORMAT
-        let header = [buf.next()?, buf.next()?, buf.next()?, buf.next()?];
+        let header = [buf.next()?, buf.next()?, buf.next()?];
         if header != FORMAT_HEADER {
             eprintln!(
                 "Headers do not match: {:?} ≠ {:?}",
@@ -133,6 +677,11 @@ impl Graphic {
             );
             return None;
         }
+        let version = buf.next()?;
+        if version > FORMAT_VERSION {
+            eprintln!("Unsupported format version: {}", version);
+            return None;
+        }
 
         // ATTRIBUTE_LIST
         let mut attributes = Vec::new();
@@ -245,6 +794,14 @@ impl Graphic {
             group.push(path);
         }
 
+        // GRADIENTS
+        let mut gradients = Vec::new();
+        if version >= 5 {
+            while let Some(gradient) = read_gradient(&mut buf)? {
+                gradients.push(gradient);
+            }
+        }
+
         // MODELS
         let mut models = Vec::new();
         'm: loop {
@@ -276,132 +833,123 @@ impl Graphic {
                     break 'g2;
                 }
 
-                let mut group_props = Vec::new();
-                'p2: loop {
-                    group_props.push(match buf.next()? {
-                        0 => break 'p2,
-                        1 => GroupProperty::FillColorRgba([
-                            buf.next()?,
-                            buf.next()?,
-                            buf.next()?,
-                            buf.next()?,
-                        ]),
-                        2 => GroupProperty::StrokeColorRgba([
-                            buf.next()?,
-                            buf.next()?,
-                            buf.next()?,
-                            buf.next()?,
-                        ]),
-                        3 => GroupProperty::StrokeWidth(f32::from_le_bytes([
-                            buf.next()?,
-                            buf.next()?,
-                            buf.next()?,
-                            buf.next()?,
-                        ])),
-                        4 => GroupProperty::JoinStyle(buf.next()?),
-                        5 => GroupProperty::FillRule(buf.next()?),
-                        6 => GroupProperty::GlyphID(u32::from_le_bytes([
-                            buf.next()?,
-                            buf.next()?,
-                            buf.next()?,
-                            buf.next()?,
-                        ])),
-                        7 => {
-                            GroupProperty::BitmapPattern(u32::from_le_bytes([
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                            ]))
-                        }
-                        8 => GroupProperty::GroupPattern(u32::from_le_bytes([
-                            buf.next()?,
-                            buf.next()?,
-                            buf.next()?,
-                            buf.next()?,
-                        ])),
-                        u => panic!("Unknown group property: {}", u),
-                    });
-                }
+                let group_props = read_group_props(&mut buf)?;
 
                 groups.push((group_id, group_props));
             }
 
             let mut frames = Vec::new();
             'f: loop {
+                // Legacy files have one list, shared by every group.
+                let lists = if version == 0 { 1 } else { groups.len() };
                 let mut transforms = Vec::new();
-                't: loop {
-                    transforms.push(match buf.next()? {
-                        0 => break 't,
-                        1 => Transform::Translate(
-                            f32::from_le_bytes([
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                            ]),
-                            f32::from_le_bytes([
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                            ]),
-                            f32::from_le_bytes([
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                            ]),
-                        ),
-                        2 => Transform::Scale(
-                            f32::from_le_bytes([
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                            ]),
-                            f32::from_le_bytes([
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                            ]),
-                            f32::from_le_bytes([
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                            ]),
-                        ),
-                        3 => Transform::Rotate(
-                            f32::from_le_bytes([
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                            ]),
-                            f32::from_le_bytes([
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                            ]),
-                            f32::from_le_bytes([
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                            ]),
-                            f32::from_le_bytes([
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                                buf.next()?,
-                            ]),
-                        ),
-                        u => panic!("Unknown transform: {}", u),
-                    });
+                for _ in 0..lists {
+                    let mut ops = Vec::new();
+                    't: loop {
+                        ops.push(match buf.next()? {
+                            0 => break 't,
+                            1 => TransformOp::Translate(
+                                f32::from_le_bytes([
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                ]),
+                                f32::from_le_bytes([
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                ]),
+                                f32::from_le_bytes([
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                ]),
+                            ),
+                            2 => TransformOp::Scale(
+                                f32::from_le_bytes([
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                ]),
+                                f32::from_le_bytes([
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                ]),
+                                f32::from_le_bytes([
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                ]),
+                            ),
+                            3 => TransformOp::Rotate(
+                                f32::from_le_bytes([
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                ]),
+                                f32::from_le_bytes([
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                ]),
+                                f32::from_le_bytes([
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                ]),
+                                f32::from_le_bytes([
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                    buf.next()?,
+                                ]),
+                            ),
+                            4 => TransformOp::Skew(
+                                read_f32(&mut buf)?,
+                                read_f32(&mut buf)?,
+                            ),
+                            5 => {
+                                let mut m = [0.0; 6];
+                                for value in m.iter_mut() {
+                                    *value = read_f32(&mut buf)?;
+                                }
+                                TransformOp::Matrix(m)
+                            }
+                            6 => TransformOp::MotionPath(
+                                read_u32(&mut buf)?,
+                                read_f32(&mut buf)?,
+                                buf.next()? != 0,
+                            ),
+                            u => panic!("Unknown transform: {}", u),
+                        });
+                    }
+                    transforms.push(ops);
                 }
+                if version == 0 {
+                    let ops = transforms.pop()?;
+                    transforms = vec![ops; groups.len()];
+                }
+                let mut properties = Vec::new();
+                if version >= 3 {
+                    for _ in 0..groups.len() {
+                        properties.push(read_group_props(&mut buf)?);
+                    }
+                }
+                let morph = if version >= 4 {
+                    read_morph(&mut buf)?
+                } else {
+                    Morph::None
+                };
                 let delay = u16::from_le_bytes([buf.next()?, buf.next()?]);
                 let animation = match buf.next()? {
                     0 => Animation::Done,
@@ -421,6 +969,27 @@ impl Graphic {
                     ])),
                     5 => Animation::Fade,
                     6 => Animation::Layer,
+                    7 => Animation::CubicBezier(
+                        read_f32(&mut buf)?,
+                        read_f32(&mut buf)?,
+                        read_f32(&mut buf)?,
+                        read_f32(&mut buf)?,
+                    ),
+                    8 => Animation::Steps(
+                        u32::from_le_bytes([
+                            buf.next()?,
+                            buf.next()?,
+                            buf.next()?,
+                            buf.next()?,
+                        ]),
+                        match buf.next()? {
+                            0 => StepPosition::JumpStart,
+                            1 => StepPosition::JumpEnd,
+                            2 => StepPosition::JumpNone,
+                            3 => StepPosition::JumpBoth,
+                            u => panic!("Unknown step position: {}", u),
+                        },
+                    ),
                     u => panic!("Unknown animation: {}", u),
                 };
 
@@ -428,6 +997,8 @@ impl Graphic {
 
                 frames.push(Frame {
                     transforms,
+                    properties,
+                    morph,
                     delay,
                     animation,
                 });
@@ -436,11 +1007,31 @@ impl Graphic {
                     break 'f;
                 }
             }
+            // PLAYBACK
+            let playback = if version >= 2 {
+                let loops = read_u32(&mut buf)?;
+                let flags = buf.next()?;
+                Playback {
+                    loops,
+                    ping_pong: flags & 1 != 0,
+                    start_delay: read_u32(&mut buf)?,
+                    duration: read_u32(&mut buf)?,
+                    color_space: if flags & 2 != 0 {
+                        ColorSpace::Linear
+                    } else {
+                        ColorSpace::Srgb
+                    },
+                }
+            } else {
+                Playback::default()
+            };
+
             models.push(Model {
                 frames,
                 groups,
                 width,
                 height,
+                playback,
             });
         }
 
@@ -449,15 +1040,34 @@ impl Graphic {
         while let Some(a) = buf.next() {
             let width = u16::from_le_bytes([a, buf.next()?]);
             let height = u16::from_le_bytes([buf.next()?, buf.next()?]);
+            if width == 0 {
+                let len = u32::from_le_bytes([
+                    buf.next()?,
+                    buf.next()?,
+                    buf.next()?,
+                    buf.next()?,
+                ]);
+                let mut uri = Vec::new();
+                for _ in 0..len {
+                    uri.push(buf.next()?);
+                }
+                let uri = String::from_utf8(uri).ok()?;
+                bitmaps.push(match height {
+                    0 => BitmapSource::External(uri),
+                    1 => BitmapSource::Graphic(uri),
+                    u => panic!("Unknown bitmap reference: {}", u),
+                });
+                continue;
+            }
             let mut srgba = Vec::new();
-            for _ in 0..(width * height * 4) {
+            for _ in 0..(usize::from(width) * usize::from(height) * 4) {
                 srgba.push(buf.next()?);
             }
-            bitmaps.push(Bitmap {
+            bitmaps.push(BitmapSource::Embedded(Bitmap {
                 width,
                 height,
                 srgba,
-            });
+            }));
         }
 
         println!("Load Success!!");
@@ -466,61 +1076,69 @@ impl Graphic {
             attributes,
             bitmaps,
             group,
+            gradients,
             models,
             vertex_list,
         })
     }
 
+    /// Compress and write the graphic.  Returns `None` if writing fails, or
+    /// if an embedded bitmap has a width of 0 (which marks a reference).
     #[cfg(feature = "zstd")]
     pub fn save<W: Write>(&self, writer: W) -> Option<()> {
         let mut encoder = Encoder::new(writer, 21).ok()?.auto_finish();
 
         // FORMAT
-        encoder.write(&FORMAT_HEADER).ok()?;
+        encoder.write_all(&FORMAT_HEADER).ok()?;
+        encoder.write_all(&[FORMAT_VERSION]).ok()?;
 
         // ATTRIBUTE_LIST
         for attribute in &self.attributes {
             match attribute {
-                Attribute::Z => encoder.write(&[1]).ok()?,
-                Attribute::UvTextureCoordinates => encoder.write(&[2]).ok()?,
-                Attribute::Rgb => encoder.write(&[3]).ok()?,
-                Attribute::Rbga => encoder.write(&[4]).ok()?,
-                Attribute::Alpha => encoder.write(&[5]).ok()?,
-                Attribute::Normal2D => encoder.write(&[6]).ok()?,
-                Attribute::Normal3D => encoder.write(&[7]).ok()?,
-                Attribute::Normal4D => encoder.write(&[8]).ok()?,
-                Attribute::StrokeWidth => encoder.write(&[9]).ok()?,
-                Attribute::UserDefined(n) => encoder.write(&[16, *n]).ok()?,
+                Attribute::Z => encoder.write_all(&[1]).ok()?,
+                Attribute::UvTextureCoordinates => {
+                    encoder.write_all(&[2]).ok()?
+                }
+                Attribute::Rgb => encoder.write_all(&[3]).ok()?,
+                Attribute::Rbga => encoder.write_all(&[4]).ok()?,
+                Attribute::Alpha => encoder.write_all(&[5]).ok()?,
+                Attribute::Normal2D => encoder.write_all(&[6]).ok()?,
+                Attribute::Normal3D => encoder.write_all(&[7]).ok()?,
+                Attribute::Normal4D => encoder.write_all(&[8]).ok()?,
+                Attribute::StrokeWidth => encoder.write_all(&[9]).ok()?,
+                Attribute::UserDefined(n) => {
+                    encoder.write_all(&[16, *n]).ok()?
+                }
             };
         }
-        encoder.write(&[0]).ok()?;
+        encoder.write_all(&[0]).ok()?;
 
         // VERTEX_LIST
         for vertex in &self.vertex_list {
-            encoder.write(&vertex.to_le_bytes()).ok()?;
+            encoder.write_all(&vertex.to_le_bytes()).ok()?;
         }
-        encoder.write(&f32::NAN.to_le_bytes()).ok()?;
+        encoder.write_all(&f32::NAN.to_le_bytes()).ok()?;
 
         // GROUP
         for group in &self.group {
             for op in group {
                 match op {
                     PathOp::Close() => {
-                        encoder.write(&[1]).ok()?;
+                        encoder.write_all(&[1]).ok()?;
                     }
                     PathOp::Move(index) => {
                         let a = index.to_le_bytes();
-                        encoder.write(&[2, a[0], a[1], a[2], a[3]]).ok()?;
+                        encoder.write_all(&[2, a[0], a[1], a[2], a[3]]).ok()?;
                     }
                     PathOp::Line(index) => {
                         let a = index.to_le_bytes();
-                        encoder.write(&[3, a[0], a[1], a[2], a[3]]).ok()?;
+                        encoder.write_all(&[3, a[0], a[1], a[2], a[3]]).ok()?;
                     }
                     PathOp::Quad(one, two) => {
                         let a = one.to_le_bytes();
                         let b = two.to_le_bytes();
                         encoder
-                            .write(&[
+                            .write_all(&[
                                 4, a[0], a[1], a[2], a[3], b[0], b[1], b[2],
                                 b[3],
                             ])
@@ -531,7 +1149,7 @@ impl Graphic {
                         let b = two.to_le_bytes();
                         let c = three.to_le_bytes();
                         encoder
-                            .write(&[
+                            .write_all(&[
                                 5, a[0], a[1], a[2], a[3], b[0], b[1], b[2],
                                 b[3], c[0], c[1], c[2], c[3],
                             ])
@@ -539,113 +1157,558 @@ impl Graphic {
                     }
                 }
             }
-            encoder.write(&[0]).ok()?;
+            encoder.write_all(&[0]).ok()?;
         }
-        encoder.write(&[0]).ok()?;
+        encoder.write_all(&[0]).ok()?;
+
+        // GRADIENTS
+        for gradient in &self.gradients {
+            write_gradient(&mut encoder, gradient)?;
+        }
+        encoder.write_all(&[0]).ok()?;
 
         // MODELS
         for model in &self.models {
-            encoder.write(&model.width.to_le_bytes()).ok()?;
-            encoder.write(&model.height.to_le_bytes()).ok()?;
+            encoder.write_all(&model.width.to_le_bytes()).ok()?;
+            encoder.write_all(&model.height.to_le_bytes()).ok()?;
 
             // GROUPS
             for (group_id, group_props) in &model.groups {
-                encoder.write(&group_id.to_le_bytes()).ok()?;
-                for prop in group_props {
-                    use GroupProperty::*;
-                    match *prop {
-                        FillColorRgba([r, g, b, a]) => {
-                            encoder.write(&[1, r, g, b, a]).ok()?;
-                        }
-                        StrokeColorRgba([r, g, b, a]) => {
-                            encoder.write(&[2, r, g, b, a]).ok()?;
-                        }
-                        StrokeWidth(width) => {
-                            encoder.write(&[3]).ok()?;
-                            encoder.write(&width.to_le_bytes()).ok()?;
-                        }
-                        JoinStyle(style) => {
-                            encoder.write(&[4, style]).ok()?;
-                        }
-                        FillRule(rule) => {
-                            encoder.write(&[5, rule]).ok()?;
-                        }
-                        GlyphID(id) => {
-                            let a = id.to_le_bytes();
-                            encoder.write(&[6, a[0], a[1], a[2], a[3]]).ok()?;
-                        }
-                        BitmapPattern(id) => {
-                            let a = id.to_le_bytes();
-                            encoder.write(&[7, a[0], a[1], a[2], a[3]]).ok()?;
-                        }
-                        GroupPattern(id) => {
-                            let a = id.to_le_bytes();
-                            encoder.write(&[8, a[0], a[1], a[2], a[3]]).ok()?;
-                        }
-                    }
-                }
-                encoder.write(&[0]).ok()?;
+                encoder.write_all(&group_id.to_le_bytes()).ok()?;
+                write_group_props(&mut encoder, group_props)?;
             }
-            encoder.write(&u32::MAX.to_le_bytes()).ok()?;
+            encoder.write_all(&u32::MAX.to_le_bytes()).ok()?;
 
             // FRAMES
             for frame in &model.frames {
-                for transform in &frame.transforms {
-                    use Transform::*;
-                    match transform {
-                        Translate(x, y, z) => {
-                            encoder.write(&[1]).ok()?;
-                            encoder.write(&x.to_le_bytes()).ok()?;
-                            encoder.write(&y.to_le_bytes()).ok()?;
-                            encoder.write(&z.to_le_bytes()).ok()?;
-                        }
-                        Scale(x, y, z) => {
-                            encoder.write(&[2]).ok()?;
-                            encoder.write(&x.to_le_bytes()).ok()?;
-                            encoder.write(&y.to_le_bytes()).ok()?;
-                            encoder.write(&z.to_le_bytes()).ok()?;
-                        }
-                        Rotate(x, y, z, w) => {
-                            encoder.write(&[3]).ok()?;
-                            encoder.write(&x.to_le_bytes()).ok()?;
-                            encoder.write(&y.to_le_bytes()).ok()?;
-                            encoder.write(&z.to_le_bytes()).ok()?;
-                            encoder.write(&w.to_le_bytes()).ok()?;
+                for i in 0..model.groups.len() {
+                    let ops = frame.transforms.get(i).map_or(&[][..], |o| o);
+                    for op in ops {
+                        use TransformOp::*;
+                        match op {
+                            Translate(x, y, z) => {
+                                encoder.write_all(&[1]).ok()?;
+                                encoder.write_all(&x.to_le_bytes()).ok()?;
+                                encoder.write_all(&y.to_le_bytes()).ok()?;
+                                encoder.write_all(&z.to_le_bytes()).ok()?;
+                            }
+                            Scale(x, y, z) => {
+                                encoder.write_all(&[2]).ok()?;
+                                encoder.write_all(&x.to_le_bytes()).ok()?;
+                                encoder.write_all(&y.to_le_bytes()).ok()?;
+                                encoder.write_all(&z.to_le_bytes()).ok()?;
+                            }
+                            Rotate(x, y, z, w) => {
+                                encoder.write_all(&[3]).ok()?;
+                                encoder.write_all(&x.to_le_bytes()).ok()?;
+                                encoder.write_all(&y.to_le_bytes()).ok()?;
+                                encoder.write_all(&z.to_le_bytes()).ok()?;
+                                encoder.write_all(&w.to_le_bytes()).ok()?;
+                            }
+                            Skew(x, y) => {
+                                encoder.write_all(&[4]).ok()?;
+                                encoder.write_all(&x.to_le_bytes()).ok()?;
+                                encoder.write_all(&y.to_le_bytes()).ok()?;
+                            }
+                            Matrix(m) => {
+                                encoder.write_all(&[5]).ok()?;
+                                for value in m {
+                                    encoder
+                                        .write_all(&value.to_le_bytes())
+                                        .ok()?;
+                                }
+                            }
+                            MotionPath(path, distance, rotate) => {
+                                encoder.write_all(&[6]).ok()?;
+                                encoder.write_all(&path.to_le_bytes()).ok()?;
+                                encoder
+                                    .write_all(&distance.to_le_bytes())
+                                    .ok()?;
+                                encoder.write_all(&[*rotate as u8]).ok()?;
+                            }
                         }
                     }
+                    encoder.write_all(&[0]).ok()?;
                 }
-                encoder.write(&[0]).ok()?;
-                encoder.write(&frame.delay.to_le_bytes()).ok()?;
+                for i in 0..model.groups.len() {
+                    let props = frame.properties.get(i).map_or(&[][..], |p| p);
+                    write_group_props(&mut encoder, props)?;
+                }
+                write_morph(&mut encoder, &frame.morph)?;
+                encoder.write_all(&frame.delay.to_le_bytes()).ok()?;
                 match frame.animation {
-                    Animation::Done => encoder.write(&[0]).ok()?,
-                    Animation::Jump => encoder.write(&[1]).ok()?,
-                    Animation::Linear => encoder.write(&[2]).ok()?,
+                    Animation::Done => encoder.write_all(&[0]).ok()?,
+                    Animation::Jump => encoder.write_all(&[1]).ok()?,
+                    Animation::Linear => encoder.write_all(&[2]).ok()?,
                     Animation::ExpA(amt_faster) => {
                         let a = amt_faster.to_le_bytes();
-                        encoder.write(&[3, a[0], a[1], a[2], a[3]]).ok()?
+                        encoder.write_all(&[3, a[0], a[1], a[2], a[3]]).ok()?
                     }
                     Animation::ExpB(amt_faster) => {
                         let a = amt_faster.to_le_bytes();
-                        encoder.write(&[4, a[0], a[1], a[2], a[3]]).ok()?
+                        encoder.write_all(&[4, a[0], a[1], a[2], a[3]]).ok()?
+                    }
+                    Animation::Fade => encoder.write_all(&[5]).ok()?,
+                    Animation::Layer => encoder.write_all(&[6]).ok()?,
+                    Animation::CubicBezier(x1, y1, x2, y2) => {
+                        encoder.write_all(&[7]).ok()?;
+                        for value in &[x1, y1, x2, y2] {
+                            encoder.write_all(&value.to_le_bytes()).ok()?;
+                        }
+                    }
+                    Animation::Steps(steps, position) => {
+                        let a = steps.to_le_bytes();
+                        let position = match position {
+                            StepPosition::JumpStart => 0,
+                            StepPosition::JumpEnd => 1,
+                            StepPosition::JumpNone => 2,
+                            StepPosition::JumpBoth => 3,
+                        };
+                        encoder
+                            .write_all(&[8, a[0], a[1], a[2], a[3], position])
+                            .ok()?;
                     }
-                    Animation::Fade => encoder.write(&[5]).ok()?,
-                    Animation::Layer => encoder.write(&[6]).ok()?,
                 };
             }
+
+            // PLAYBACK
+            let playback = &model.playback;
+            encoder.write_all(&playback.loops.to_le_bytes()).ok()?;
+            let linear = playback.color_space == ColorSpace::Linear;
+            let flags = playback.ping_pong as u8 | (linear as u8) << 1;
+            encoder.write_all(&[flags]).ok()?;
+            encoder
+                .write_all(&playback.start_delay.to_le_bytes())
+                .ok()?;
+            encoder.write_all(&playback.duration.to_le_bytes()).ok()?;
         }
-        encoder.write(&f32::NAN.to_le_bytes()).ok()?;
+        encoder.write_all(&f32::NAN.to_le_bytes()).ok()?;
 
         // BITMAPS
         for bitmap in &self.bitmaps {
-            encoder.write(&bitmap.width.to_le_bytes()).ok()?;
-            encoder.write(&bitmap.height.to_le_bytes()).ok()?;
-            encoder.write(&bitmap.srgba).ok()?;
+            match bitmap {
+                BitmapSource::Embedded(bitmap) => {
+                    // A width of 0 marks a reference.
+                    if bitmap.width == 0 {
+                        return None;
+                    }
+                    encoder.write_all(&bitmap.width.to_le_bytes()).ok()?;
+                    encoder.write_all(&bitmap.height.to_le_bytes()).ok()?;
+                    encoder.write_all(&bitmap.srgba).ok()?;
+                }
+                BitmapSource::External(uri) => {
+                    let len: u32 = uri.len().try_into().ok()?;
+                    encoder.write_all(&[0, 0, 0, 0]).ok()?;
+                    encoder.write_all(&len.to_le_bytes()).ok()?;
+                    encoder.write_all(uri.as_bytes()).ok()?;
+                }
+                BitmapSource::Graphic(uri) => {
+                    let len: u32 = uri.len().try_into().ok()?;
+                    encoder.write_all(&[0, 0, 1, 0]).ok()?;
+                    encoder.write_all(&len.to_le_bytes()).ok()?;
+                    encoder.write_all(uri.as_bytes()).ok()?;
+                }
+            }
         }
 
         Some(())
     }
 }
 
+/// Read the vertex positions of a keyframe.
+fn read_morph(buf: &mut impl Iterator<Item = u8>) -> Option<Morph> {
+    Some(match buf.next()? {
+        0 => Morph::None,
+        1 => {
+            let count = read_u32(buf)?;
+            let mut positions = Vec::new();
+            for _ in 0..count {
+                positions.push([read_f32(buf)?, read_f32(buf)?]);
+            }
+            Morph::Snapshot(positions)
+        }
+        2 => {
+            let count = read_u32(buf)?;
+            let mut positions = Vec::new();
+            for _ in 0..count {
+                let index = read_u32(buf)?;
+                positions.push((index, [read_f32(buf)?, read_f32(buf)?]));
+            }
+            Morph::Sparse(positions)
+        }
+        u => panic!("Unknown morph: {}", u),
+    })
+}
+
+/// Write the vertex positions of a keyframe.
+#[cfg(feature = "zstd")]
+fn write_morph<W: Write>(encoder: &mut W, morph: &Morph) -> Option<()> {
+    match morph {
+        Morph::None => encoder.write_all(&[0]).ok()?,
+        Morph::Snapshot(positions) => {
+            let count: u32 = positions.len().try_into().ok()?;
+            encoder.write_all(&[1]).ok()?;
+            encoder.write_all(&count.to_le_bytes()).ok()?;
+            for [x, y] in positions {
+                encoder.write_all(&x.to_le_bytes()).ok()?;
+                encoder.write_all(&y.to_le_bytes()).ok()?;
+            }
+        }
+        Morph::Sparse(positions) => {
+            let count: u32 = positions.len().try_into().ok()?;
+            encoder.write_all(&[2]).ok()?;
+            encoder.write_all(&count.to_le_bytes()).ok()?;
+            for (index, [x, y]) in positions {
+                encoder.write_all(&index.to_le_bytes()).ok()?;
+                encoder.write_all(&x.to_le_bytes()).ok()?;
+                encoder.write_all(&y.to_le_bytes()).ok()?;
+            }
+        }
+    }
+    Some(())
+}
+
+/// Read a gradient, or `None` inside the `Some` at the end of the list.
+fn read_gradient(
+    buf: &mut impl Iterator<Item = u8>,
+) -> Option<Option<Gradient>> {
+    let shape = match buf.next()? {
+        0 => return Some(None),
+        1 => GradientShape::Linear {
+            start: [read_f32(buf)?, read_f32(buf)?],
+            end: [read_f32(buf)?, read_f32(buf)?],
+        },
+        2 => GradientShape::Radial {
+            center: [read_f32(buf)?, read_f32(buf)?],
+            radius: read_f32(buf)?,
+            focus: [read_f32(buf)?, read_f32(buf)?],
+        },
+        3 => GradientShape::Conic {
+            center: [read_f32(buf)?, read_f32(buf)?],
+            angle: read_f32(buf)?,
+        },
+        u => panic!("Unknown gradient shape: {}", u),
+    };
+    let spread = match buf.next()? {
+        0 => Spread::Pad,
+        1 => Spread::Reflect,
+        2 => Spread::Repeat,
+        u => panic!("Unknown spread: {}", u),
+    };
+    let mut transform = [0.0; 6];
+    for value in transform.iter_mut() {
+        *value = read_f32(buf)?;
+    }
+    let count = read_u32(buf)?;
+    let mut stops = Vec::new();
+    for _ in 0..count {
+        let offset = read_f32(buf)?;
+        let color = [buf.next()?, buf.next()?, buf.next()?, buf.next()?];
+        stops.push((offset, color));
+    }
+    Some(Some(Gradient {
+        shape,
+        stops,
+        spread,
+        transform: Affine2::new(transform),
+    }))
+}
+
+/// Write a gradient.
+#[cfg(feature = "zstd")]
+fn write_gradient<W: Write>(
+    encoder: &mut W,
+    gradient: &Gradient,
+) -> Option<()> {
+    let values = match gradient.shape {
+        GradientShape::Linear { start, end } => {
+            encoder.write_all(&[1]).ok()?;
+            vec![start[0], start[1], end[0], end[1]]
+        }
+        GradientShape::Radial {
+            center,
+            radius,
+            focus,
+        } => {
+            encoder.write_all(&[2]).ok()?;
+            vec![center[0], center[1], radius, focus[0], focus[1]]
+        }
+        GradientShape::Conic { center, angle } => {
+            encoder.write_all(&[3]).ok()?;
+            vec![center[0], center[1], angle]
+        }
+    };
+    for value in values {
+        encoder.write_all(&value.to_le_bytes()).ok()?;
+    }
+    let spread = match gradient.spread {
+        Spread::Pad => 0,
+        Spread::Reflect => 1,
+        Spread::Repeat => 2,
+    };
+    encoder.write_all(&[spread]).ok()?;
+    for value in &gradient.transform.to_array() {
+        encoder.write_all(&value.to_le_bytes()).ok()?;
+    }
+    let count: u32 = gradient.stops.len().try_into().ok()?;
+    encoder.write_all(&count.to_le_bytes()).ok()?;
+    for (offset, color) in &gradient.stops {
+        encoder.write_all(&offset.to_le_bytes()).ok()?;
+        encoder.write_all(color).ok()?;
+    }
+    Some(())
+}
+
+/// Read a property list of a group, up to its end marker.
+fn read_group_props(
+    buf: &mut impl Iterator<Item = u8>,
+) -> Option<Vec<GroupProperty>> {
+    let mut group_props = Vec::new();
+    'p2: loop {
+        group_props.push(match buf.next()? {
+            0 => break 'p2,
+            1 => GroupProperty::FillColorRgba([
+                buf.next()?,
+                buf.next()?,
+                buf.next()?,
+                buf.next()?,
+            ]),
+            2 => GroupProperty::StrokeColorRgba([
+                buf.next()?,
+                buf.next()?,
+                buf.next()?,
+                buf.next()?,
+            ]),
+            3 => GroupProperty::StrokeWidth(f32::from_le_bytes([
+                buf.next()?,
+                buf.next()?,
+                buf.next()?,
+                buf.next()?,
+            ])),
+            4 => GroupProperty::JoinStyle(match buf.next()? {
+                0 => JoinStyle::Miter,
+                1 => JoinStyle::Round,
+                2 => JoinStyle::Bevel,
+                u => panic!("Unknown join style: {}", u),
+            }),
+            5 => GroupProperty::FillRule(match buf.next()? {
+                0 => FillRule::NonZero,
+                1 => FillRule::EvenOdd,
+                u => panic!("Unknown fill rule: {}", u),
+            }),
+            6 => GroupProperty::GlyphID(u32::from_le_bytes([
+                buf.next()?,
+                buf.next()?,
+                buf.next()?,
+                buf.next()?,
+            ])),
+            7 => GroupProperty::BitmapPattern(u32::from_le_bytes([
+                buf.next()?,
+                buf.next()?,
+                buf.next()?,
+                buf.next()?,
+            ])),
+            8 => GroupProperty::GroupPattern(u32::from_le_bytes([
+                buf.next()?,
+                buf.next()?,
+                buf.next()?,
+                buf.next()?,
+            ])),
+            9 => GroupProperty::Pivot(read_f32(buf)?, read_f32(buf)?),
+            10 => GroupProperty::Opacity(read_f32(buf)?),
+            11 => GroupProperty::Parent(read_u32(buf)?),
+            12 => GroupProperty::LineCap(match buf.next()? {
+                0 => LineCap::Butt,
+                1 => LineCap::Round,
+                2 => LineCap::Square,
+                u => panic!("Unknown line cap: {}", u),
+            }),
+            13 => GroupProperty::MiterLimit(read_f32(buf)?),
+            14 => {
+                let count = read_u32(buf)?;
+                let mut pattern = Vec::new();
+                for _ in 0..count {
+                    pattern.push(read_f32(buf)?);
+                }
+                GroupProperty::Dash {
+                    pattern,
+                    offset: read_f32(buf)?,
+                }
+            }
+            15 => GroupProperty::FillGradient(read_u32(buf)?),
+            16 => GroupProperty::StrokeGradient(read_u32(buf)?),
+            17 => {
+                let mut m = [0.0; 6];
+                for value in m.iter_mut() {
+                    *value = read_f32(buf)?;
+                }
+                GroupProperty::PatternTransform(m)
+            }
+            18 => GroupProperty::Sampling(match buf.next()? {
+                0 => Sampling::Nearest,
+                1 => Sampling::Bilinear,
+                u => panic!("Unknown sampling: {}", u),
+            }),
+            19 => GroupProperty::Wrap(match buf.next()? {
+                0 => Wrap::Repeat,
+                1 => Wrap::Clamp,
+                u => panic!("Unknown wrap: {}", u),
+            }),
+            20 => GroupProperty::TileSize(read_f32(buf)?, read_f32(buf)?),
+            21 => GroupProperty::TileSpacing(read_f32(buf)?, read_f32(buf)?),
+            u => panic!("Unknown group property: {}", u),
+        });
+    }
+    Some(group_props)
+}
+
+/// Write a property list of a group, and its end marker.
+#[cfg(feature = "zstd")]
+fn write_group_props<W: Write>(
+    encoder: &mut W,
+    group_props: &[GroupProperty],
+) -> Option<()> {
+    for prop in group_props {
+        use GroupProperty::*;
+        match *prop {
+            FillColorRgba([r, g, b, a]) => {
+                encoder.write_all(&[1, r, g, b, a]).ok()?;
+            }
+            StrokeColorRgba([r, g, b, a]) => {
+                encoder.write_all(&[2, r, g, b, a]).ok()?;
+            }
+            StrokeWidth(width) => {
+                encoder.write_all(&[3]).ok()?;
+                encoder.write_all(&width.to_le_bytes()).ok()?;
+            }
+            JoinStyle(style) => {
+                let style = match style {
+                    self::JoinStyle::Miter => 0,
+                    self::JoinStyle::Round => 1,
+                    self::JoinStyle::Bevel => 2,
+                };
+                encoder.write_all(&[4, style]).ok()?;
+            }
+            FillRule(rule) => {
+                let rule = match rule {
+                    self::FillRule::NonZero => 0,
+                    self::FillRule::EvenOdd => 1,
+                };
+                encoder.write_all(&[5, rule]).ok()?;
+            }
+            GlyphID(id) => {
+                let a = id.to_le_bytes();
+                encoder.write_all(&[6, a[0], a[1], a[2], a[3]]).ok()?;
+            }
+            BitmapPattern(id) => {
+                let a = id.to_le_bytes();
+                encoder.write_all(&[7, a[0], a[1], a[2], a[3]]).ok()?;
+            }
+            GroupPattern(id) => {
+                let a = id.to_le_bytes();
+                encoder.write_all(&[8, a[0], a[1], a[2], a[3]]).ok()?;
+            }
+            Pivot(x, y) => {
+                encoder.write_all(&[9]).ok()?;
+                encoder.write_all(&x.to_le_bytes()).ok()?;
+                encoder.write_all(&y.to_le_bytes()).ok()?;
+            }
+            Opacity(opacity) => {
+                encoder.write_all(&[10]).ok()?;
+                encoder.write_all(&opacity.to_le_bytes()).ok()?;
+            }
+            Parent(parent) => {
+                encoder.write_all(&[11]).ok()?;
+                encoder.write_all(&parent.to_le_bytes()).ok()?;
+            }
+            LineCap(cap) => {
+                let cap = match cap {
+                    self::LineCap::Butt => 0,
+                    self::LineCap::Round => 1,
+                    self::LineCap::Square => 2,
+                };
+                encoder.write_all(&[12, cap]).ok()?;
+            }
+            MiterLimit(limit) => {
+                encoder.write_all(&[13]).ok()?;
+                encoder.write_all(&limit.to_le_bytes()).ok()?;
+            }
+            Dash {
+                ref pattern,
+                offset,
+            } => {
+                let count: u32 = pattern.len().try_into().ok()?;
+                encoder.write_all(&[14]).ok()?;
+                encoder.write_all(&count.to_le_bytes()).ok()?;
+                for length in pattern {
+                    encoder.write_all(&length.to_le_bytes()).ok()?;
+                }
+                encoder.write_all(&offset.to_le_bytes()).ok()?;
+            }
+            FillGradient(gradient) => {
+                encoder.write_all(&[15]).ok()?;
+                encoder.write_all(&gradient.to_le_bytes()).ok()?;
+            }
+            StrokeGradient(gradient) => {
+                encoder.write_all(&[16]).ok()?;
+                encoder.write_all(&gradient.to_le_bytes()).ok()?;
+            }
+            PatternTransform(m) => {
+                encoder.write_all(&[17]).ok()?;
+                for value in &m {
+                    encoder.write_all(&value.to_le_bytes()).ok()?;
+                }
+            }
+            Sampling(sampling) => {
+                let sampling = match sampling {
+                    self::Sampling::Nearest => 0,
+                    self::Sampling::Bilinear => 1,
+                };
+                encoder.write_all(&[18, sampling]).ok()?;
+            }
+            Wrap(wrap) => {
+                let wrap = match wrap {
+                    self::Wrap::Repeat => 0,
+                    self::Wrap::Clamp => 1,
+                };
+                encoder.write_all(&[19, wrap]).ok()?;
+            }
+            TileSize(width, height) => {
+                encoder.write_all(&[20]).ok()?;
+                encoder.write_all(&width.to_le_bytes()).ok()?;
+                encoder.write_all(&height.to_le_bytes()).ok()?;
+            }
+            TileSpacing(x, y) => {
+                encoder.write_all(&[21]).ok()?;
+                encoder.write_all(&x.to_le_bytes()).ok()?;
+                encoder.write_all(&y.to_le_bytes()).ok()?;
+            }
+        }
+    }
+    encoder.write_all(&[0]).ok()?;
+    Some(())
+}
+
+/// Read a little-endian `f32`.
+fn read_f32(buf: &mut impl Iterator<Item = u8>) -> Option<f32> {
+    Some(f32::from_le_bytes([
+        buf.next()?,
+        buf.next()?,
+        buf.next()?,
+        buf.next()?,
+    ]))
+}
+
+/// Read a little-endian `u32`.
+fn read_u32(buf: &mut impl Iterator<Item = u8>) -> Option<u32> {
+    Some(u32::from_le_bytes([
+        buf.next()?,
+        buf.next()?,
+        buf.next()?,
+        buf.next()?,
+    ]))
+}
+
 /// Helper function.
 pub fn clone_into_array<A, T>(slice: &[T]) -> A
 where
@@ -656,3 +1719,616 @@ where
     <A as AsMut<[T]>>::as_mut(&mut a).clone_from_slice(slice);
     a
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkerboard(width: u16, height: u16) -> Bitmap {
+        let mut srgba = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let v = if (x + y) % 2 == 0 { 0xFF } else { 0x00 };
+                srgba.extend_from_slice(&[v, x as u8, y as u8, 0x80]);
+            }
+        }
+        Bitmap {
+            width,
+            height,
+            srgba,
+        }
+    }
+
+    #[test]
+    fn raster_round_trip() {
+        let bitmap = checkerboard(3, 2);
+        let raster = bitmap.to_raster().unwrap();
+        assert_eq!(raster.width(), 3);
+        assert_eq!(raster.height(), 2);
+        let copy = Bitmap::from_raster(&raster).unwrap();
+        assert_eq!((copy.width, copy.height), (3, 2));
+        assert_eq!(copy.srgba, bitmap.srgba);
+    }
+
+    #[test]
+    fn raster_wrong_length() {
+        let mut bitmap = checkerboard(3, 2);
+        bitmap.srgba.pop();
+        assert!(bitmap.to_raster().is_none());
+        bitmap.srgba.extend_from_slice(&[0; 5]);
+        assert!(bitmap.to_raster().is_none());
+    }
+
+    #[test]
+    fn vertex_attributes() {
+        let graphic = Graphic {
+            attributes: vec![Attribute::Z, Attribute::Rgb, Attribute::Alpha],
+            vertex_list: vec![
+                0.0, 1.0, 2.0, 0.1, 0.2, 0.3, 0.4, //
+                5.0, 6.0, 7.0, 0.5, 0.6, 0.7, 0.8, //
+            ],
+            group: vec![],
+            gradients: vec![],
+            models: vec![],
+            bitmaps: vec![],
+        };
+        assert_eq!(graphic.stride(), 7);
+        assert_eq!(graphic.vertex(1), Some([5.0, 6.0]));
+        let rgb: &[f32] = &[0.5, 0.6, 0.7];
+        assert_eq!(graphic.attribute(1, Attribute::Rgb), Some(rgb));
+        let alpha: &[f32] = &[0.4];
+        assert_eq!(graphic.attribute(0, Attribute::Alpha), Some(alpha));
+        assert_eq!(graphic.attribute(0, Attribute::Rbga), None);
+        assert_eq!(graphic.attribute(2, Attribute::Rgb), None);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn large_bitmap_round_trip() {
+        // 200 × 100 × 4 bytes doesn't fit in a u16.
+        let bitmap = checkerboard(200, 100);
+        let graphic = Graphic {
+            attributes: vec![],
+            vertex_list: vec![],
+            group: vec![],
+            gradients: vec![],
+            models: vec![],
+            bitmaps: vec![BitmapSource::Embedded(checkerboard(200, 100))],
+        };
+        let mut file = Vec::new();
+        graphic.save(&mut file).unwrap();
+        let loaded = Graphic::load(file.as_slice()).unwrap();
+        assert_eq!(loaded.bitmaps.len(), 1);
+        let loaded = loaded.bitmaps[0].embedded().unwrap();
+        assert_eq!((loaded.width, loaded.height), (200, 100));
+        assert_eq!(loaded.srgba, bitmap.srgba);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn reference_round_trip() {
+        let graphic = Graphic {
+            attributes: vec![],
+            vertex_list: vec![],
+            group: vec![],
+            gradients: vec![],
+            models: vec![],
+            bitmaps: vec![
+                BitmapSource::External("textures/a.png".to_string()),
+                BitmapSource::Embedded(checkerboard(2, 0)),
+                BitmapSource::Graphic("file://b.rvg".to_string()),
+                BitmapSource::Embedded(checkerboard(1, 1)),
+            ],
+        };
+        let mut file = Vec::new();
+        graphic.save(&mut file).unwrap();
+        let loaded = Graphic::load(file.as_slice()).unwrap();
+        assert_eq!(loaded.bitmaps.len(), 4);
+        match &loaded.bitmaps[0] {
+            BitmapSource::External(uri) => assert_eq!(uri, "textures/a.png"),
+            _ => panic!("Not an external bitmap"),
+        }
+        let empty = loaded.bitmaps[1].embedded().unwrap();
+        assert_eq!((empty.width, empty.height), (2, 0));
+        match &loaded.bitmaps[2] {
+            BitmapSource::Graphic(uri) => assert_eq!(uri, "file://b.rvg"),
+            _ => panic!("Not a graphic"),
+        }
+        let pixel = loaded.bitmaps[3].embedded().unwrap();
+        assert_eq!(pixel.srgba, checkerboard(1, 1).srgba);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn save_zero_width_bitmap() {
+        let graphic = Graphic {
+            attributes: vec![],
+            vertex_list: vec![],
+            group: vec![],
+            gradients: vec![],
+            models: vec![],
+            bitmaps: vec![BitmapSource::Embedded(checkerboard(0, 3))],
+        };
+        assert!(graphic.save(Vec::new()).is_none());
+    }
+
+    /// Two lines as groups 0 and 1, and a model drawing both.
+    fn lines() -> Graphic {
+        Graphic {
+            attributes: vec![],
+            vertex_list: vec![0.0, 0.0, 1.0, 0.0],
+            group: (0..2)
+                .map(|_| vec![PathOp::Move(0), PathOp::Line(1)])
+                .collect(),
+            gradients: vec![],
+            models: vec![Model {
+                width: 8.0,
+                height: 8.0,
+                groups: vec![(0, vec![]), (1, vec![])],
+                frames: vec![Frame {
+                    transforms: vec![],
+                    properties: vec![],
+                    morph: Morph::None,
+                    delay: 0,
+                    animation: Animation::Done,
+                }],
+                playback: Playback::default(),
+            }],
+            bitmaps: vec![],
+        }
+    }
+
+    /// Save and load a graphic.
+    #[cfg(feature = "zstd")]
+    fn round_trip(graphic: &Graphic) -> Graphic {
+        let mut file = Vec::new();
+        graphic.save(&mut file).unwrap();
+        Graphic::load(file.as_slice()).unwrap()
+    }
+
+    /// Compress a file written by hand, to test the layouts of older
+    /// versions.
+    #[cfg(feature = "zstd")]
+    fn compress(file: &[u8]) -> Vec<u8> {
+        zstd::stream::encode_all(file, 0).unwrap()
+    }
+
+    /// A file with two lines as groups 0 and 1, and a model drawing both
+    /// with `frames`.
+    #[cfg(feature = "zstd")]
+    fn legacy(version: u8, frames: &[u8]) -> Vec<u8> {
+        let mut file = vec![b'r', b'V', b'g', version, 0];
+        for v in &[0.0f32, 0.0, 1.0, 0.0, f32::NAN] {
+            file.extend(&v.to_le_bytes());
+        }
+        for _ in 0..2 {
+            file.extend(&[2, 0, 0, 0, 0, 3, 1, 0, 0, 0, 0]);
+        }
+        file.push(0);
+        file.extend(&8.0f32.to_le_bytes());
+        file.extend(&8.0f32.to_le_bytes());
+        file.extend(&[0, 0, 0, 0, 0, 1, 0, 0, 0, 0]);
+        file.extend(&u32::MAX.to_le_bytes());
+        file.extend(frames);
+        if version >= 2 {
+            // Play once.
+            file.extend(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        }
+        file.extend(&f32::NAN.to_le_bytes());
+        file
+    }
+
+    #[cfg(feature = "zstd")]
+    fn translate(x: f32, y: f32) -> Vec<u8> {
+        let mut op = vec![1];
+        for v in &[x, y, 0.0] {
+            op.extend(&v.to_le_bytes());
+        }
+        op
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn load_version_0() {
+        // One list shared by both groups, 100ms linear, then done.
+        let mut frames = translate(1.0, 2.0);
+        frames.extend(&[0, 100, 0, 2]);
+        frames.extend(&[0, 0, 0, 0]);
+        let file = compress(&legacy(0, &frames));
+        let graphic = Graphic::load(file.as_slice()).unwrap();
+        let frames = &graphic.models[0].frames;
+        assert_eq!(frames.len(), 2);
+        let ops = vec![TransformOp::Translate(1.0, 2.0, 0.0)];
+        assert_eq!(frames[0].transforms, vec![ops.clone(), ops]);
+        assert_eq!(frames[0].delay, 100);
+        assert!(frames[0].animation == Animation::Linear);
+        assert_eq!(frames[1].transforms, vec![vec![], vec![]]);
+        assert!(frames[1].animation == Animation::Done);
+        assert_eq!(graphic.models[0].playback, Playback::default());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn load_version_1() {
+        let mut frames = translate(1.0, 2.0);
+        frames.push(0);
+        frames.extend(&translate(3.0, 4.0));
+        frames.extend(&[0, 0, 0, 0]);
+        let file = compress(&legacy(1, &frames));
+        let graphic = Graphic::load(file.as_slice()).unwrap();
+        let frames = &graphic.models[0].frames;
+        assert_eq!(frames.len(), 1);
+        assert_eq!(
+            frames[0].transforms,
+            vec![
+                vec![TransformOp::Translate(1.0, 2.0, 0.0)],
+                vec![TransformOp::Translate(3.0, 4.0, 0.0)],
+            ]
+        );
+        assert_eq!(graphic.models[0].playback, Playback::default());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn load_future_version() {
+        let file = compress(&legacy(FORMAT_VERSION + 1, &[0, 0, 0, 0, 0]));
+        assert!(Graphic::load(file.as_slice()).is_none());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn frame_round_trip() {
+        let mut graphic = lines();
+        let ops = vec![
+            TransformOp::Rotate(0.0, 0.0, 1.0, 0.5),
+            TransformOp::Scale(2.0, 3.0, 1.0),
+            TransformOp::Skew(0.25, -0.5),
+            TransformOp::Matrix([1.0, 2.0, 3.0, 4.0, 5.0, 6.0]),
+            TransformOp::MotionPath(1, 0.75, true),
+        ];
+        graphic.models[0].frames = vec![
+            Frame {
+                transforms: vec![vec![], ops.clone()],
+                properties: vec![],
+                morph: Morph::None,
+                delay: 250,
+                animation: Animation::ExpA(2.0),
+            },
+            Frame {
+                // The second list is missing, and saved as empty.
+                transforms: vec![ops.clone()],
+                properties: vec![],
+                morph: Morph::None,
+                delay: 0,
+                animation: Animation::Done,
+            },
+        ];
+        let graphic = round_trip(&graphic);
+        let frames = &graphic.models[0].frames;
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].transforms, vec![vec![], ops.clone()]);
+        assert_eq!(frames[0].delay, 250);
+        assert!(frames[0].animation == Animation::ExpA(2.0));
+        assert_eq!(frames[1].transforms, vec![ops, vec![]]);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn pivot_round_trip() {
+        let mut graphic = lines();
+        graphic.models[0].groups[1]
+            .1
+            .push(GroupProperty::Pivot(4.0, -2.5));
+        let graphic = round_trip(&graphic);
+        let model = &graphic.models[0];
+        assert_eq!(model.pivot(0), (0.0, 0.0));
+        assert_eq!(model.pivot(1), (4.0, -2.5));
+        assert_eq!(model.pivot(2), (0.0, 0.0));
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn parent_round_trip() {
+        let mut graphic = lines();
+        graphic.models[0].groups[0].1.push(GroupProperty::Parent(7));
+        graphic.models[0].groups[1].1.push(GroupProperty::Parent(0));
+        let graphic = round_trip(&graphic);
+        let model = &graphic.models[0];
+        // Parents that don't exist are ignored.
+        assert!(matches!(model.groups[0].1[..], [GroupProperty::Parent(7)]));
+        assert_eq!(model.parent(0), None);
+        assert_eq!(model.parent(1), Some(0));
+        assert_eq!(model.parent(2), None);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn fill_rule_round_trip() {
+        let mut graphic = lines();
+        graphic.models[0].groups[1]
+            .1
+            .push(GroupProperty::FillRule(FillRule::EvenOdd));
+        let graphic = round_trip(&graphic);
+        let model = &graphic.models[0];
+        assert_eq!(model.fill_rule(0), FillRule::NonZero);
+        assert_eq!(model.fill_rule(1), FillRule::EvenOdd);
+        assert_eq!(model.fill_rule(2), FillRule::NonZero);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn stroke_style_round_trip() {
+        let mut graphic = lines();
+        graphic.models[0].groups[1].1 = vec![
+            GroupProperty::JoinStyle(JoinStyle::Bevel),
+            GroupProperty::LineCap(LineCap::Square),
+            GroupProperty::MiterLimit(2.5),
+        ];
+        let graphic = round_trip(&graphic);
+        let model = &graphic.models[0];
+        assert_eq!(model.join_style(0), JoinStyle::Miter);
+        assert_eq!(model.line_cap(0), LineCap::Butt);
+        assert_eq!(model.miter_limit(0), 4.0);
+        assert_eq!(model.join_style(1), JoinStyle::Bevel);
+        assert_eq!(model.line_cap(1), LineCap::Square);
+        assert_eq!(model.miter_limit(1), 2.5);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn dash_round_trip() {
+        let mut graphic = lines();
+        graphic.models[0].groups[1].1 = vec![GroupProperty::Dash {
+            pattern: vec![4.0, 2.0, 1.0],
+            offset: 1.5,
+        }];
+        let graphic = round_trip(&graphic);
+        let model = &graphic.models[0];
+        assert_eq!(model.dash(0), None);
+        assert_eq!(model.dash(1), Some((&[4.0, 2.0, 1.0][..], 1.5)));
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn pattern_round_trip() {
+        let mut graphic = lines();
+        graphic.bitmaps = vec![BitmapSource::Embedded(checkerboard(2, 2))];
+        graphic.models[0].groups[1].1 = vec![
+            GroupProperty::BitmapPattern(0),
+            GroupProperty::PatternTransform([2.0, 0.0, 0.0, 3.0, 1.0, -1.0]),
+            GroupProperty::Sampling(Sampling::Nearest),
+            GroupProperty::Wrap(Wrap::Clamp),
+        ];
+        let graphic = round_trip(&graphic);
+        let model = &graphic.models[0];
+        assert_eq!(model.bitmap_pattern(0), None);
+        assert_eq!(model.pattern_transform(0).apply(1.0, 1.0), (1.0, 1.0));
+        assert_eq!(model.sampling(0), Sampling::Bilinear);
+        assert_eq!(model.wrap(0), Wrap::Repeat);
+        assert_eq!(model.bitmap_pattern(1), Some(0));
+        assert_eq!(model.pattern_transform(1).apply(1.0, 1.0), (3.0, 2.0));
+        assert_eq!(model.sampling(1), Sampling::Nearest);
+        assert_eq!(model.wrap(1), Wrap::Clamp);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn tile_round_trip() {
+        let mut graphic = lines();
+        graphic.models[0].groups[1].1 = vec![
+            GroupProperty::GroupPattern(0),
+            GroupProperty::TileSize(2.0, 3.0),
+            GroupProperty::TileSpacing(0.5, 1.0),
+        ];
+        let graphic = round_trip(&graphic);
+        let model = &graphic.models[0];
+        assert_eq!(model.group_pattern(0), None);
+        assert_eq!(model.tile_size(0), None);
+        assert_eq!(model.tile_spacing(0), (0.0, 0.0));
+        assert_eq!(model.group_pattern(1), Some(0));
+        assert_eq!(model.tile_size(1), Some((2.0, 3.0)));
+        assert_eq!(model.tile_spacing(1), (0.5, 1.0));
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn easing_round_trip() {
+        let mut graphic = lines();
+        let animations = [
+            Animation::CubicBezier(0.25, 0.1, 0.25, 1.0),
+            Animation::Steps(4, StepPosition::JumpStart),
+            Animation::Steps(1, StepPosition::JumpNone),
+            Animation::Done,
+        ];
+        graphic.models[0].frames = animations
+            .iter()
+            .map(|animation| Frame {
+                transforms: vec![],
+                properties: vec![],
+                morph: Morph::None,
+                delay: 10,
+                animation: *animation,
+            })
+            .collect();
+        let graphic = round_trip(&graphic);
+        let frames = &graphic.models[0].frames;
+        assert_eq!(frames.len(), animations.len());
+        for (frame, animation) in frames.iter().zip(&animations) {
+            assert_eq!(frame.animation, *animation);
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn playback_round_trip() {
+        let mut graphic = lines();
+        let playback = Playback {
+            loops: 3,
+            ping_pong: true,
+            start_delay: 500,
+            duration: 70_000,
+            color_space: ColorSpace::Linear,
+        };
+        graphic.models[0].playback = playback;
+        let graphic = round_trip(&graphic);
+        assert_eq!(graphic.models[0].playback, playback);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn load_version_2() {
+        let mut frames = translate(1.0, 2.0);
+        frames.extend(&[0, 0, 0, 0, 0]);
+        let mut file = legacy(2, &frames);
+        // Loop forever, ping-pong, after 1 second.
+        let playback = file.len() - 4 - 13;
+        file[playback..playback + 9]
+            .copy_from_slice(&[0, 0, 0, 0, 1, 0xE8, 0x03, 0, 0]);
+        let graphic = Graphic::load(compress(&file).as_slice()).unwrap();
+        let model = &graphic.models[0];
+        assert_eq!(model.frames.len(), 1);
+        assert!(model.frames[0].properties.iter().all(|p| p.is_empty()));
+        assert_eq!(
+            model.playback,
+            Playback {
+                loops: 0,
+                ping_pong: true,
+                start_delay: 1000,
+                duration: 0,
+                color_space: ColorSpace::Srgb,
+            }
+        );
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn properties_round_trip() {
+        let mut graphic = lines();
+        let frame = &mut graphic.models[0].frames[0];
+        frame.properties = vec![
+            vec![
+                GroupProperty::Opacity(0.5),
+                GroupProperty::FillColorRgba([1, 2, 3, 4]),
+            ],
+            vec![GroupProperty::StrokeWidth(3.0)],
+        ];
+        let graphic = round_trip(&graphic);
+        let properties = &graphic.models[0].frames[0].properties;
+        assert_eq!(properties.len(), 2);
+        match properties[0][..] {
+            [GroupProperty::Opacity(o), GroupProperty::FillColorRgba(c)] => {
+                assert_eq!((o, c), (0.5, [1, 2, 3, 4]))
+            }
+            _ => panic!("Wrong properties"),
+        }
+        match properties[1][..] {
+            [GroupProperty::StrokeWidth(w)] => assert_eq!(w, 3.0),
+            _ => panic!("Wrong properties"),
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn load_version_3() {
+        // Group 1 is red at the first frame.
+        let mut frames = vec![0, 0, 0, 1, 255, 0, 0, 255, 0];
+        frames.extend(&[0, 0, 0]);
+        let graphic = Graphic::load(compress(&legacy(3, &frames)).as_slice());
+        let frame = &graphic.unwrap().models[0].frames[0];
+        assert_eq!(frame.morph, Morph::None);
+        assert_eq!(frame.properties.len(), 2);
+        match frame.properties[1][..] {
+            [GroupProperty::FillColorRgba(c)] => {
+                assert_eq!(c, [255, 0, 0, 255])
+            }
+            _ => panic!("Wrong properties"),
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn morph_round_trip() {
+        let mut graphic = lines();
+        let morphs = vec![
+            Morph::Snapshot(vec![[1.0, 2.0], [3.0, 4.0]]),
+            Morph::Sparse(vec![(1, [5.0, 6.0])]),
+            Morph::None,
+        ];
+        graphic.models[0].frames = morphs
+            .iter()
+            .map(|morph| Frame {
+                transforms: vec![],
+                properties: vec![],
+                morph: morph.clone(),
+                delay: 10,
+                animation: Animation::Linear,
+            })
+            .collect();
+        graphic.models[0].frames[2].animation = Animation::Done;
+        let graphic = round_trip(&graphic);
+        let frames = &graphic.models[0].frames;
+        assert_eq!(frames.len(), morphs.len());
+        for (frame, morph) in frames.iter().zip(&morphs) {
+            assert_eq!(frame.morph, *morph);
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn load_version_4() {
+        // Vertex 1 of group 0 moves at the first frame.
+        let mut frames = vec![0, 0, 0, 0, 2, 1, 0, 0, 0, 1, 0, 0, 0];
+        frames.extend(&5.0f32.to_le_bytes());
+        frames.extend(&6.0f32.to_le_bytes());
+        frames.extend(&[0, 0, 0]);
+        let graphic = Graphic::load(compress(&legacy(4, &frames)).as_slice());
+        let graphic = graphic.unwrap();
+        assert!(graphic.gradients.is_empty());
+        let frame = &graphic.models[0].frames[0];
+        assert_eq!(frame.morph, Morph::Sparse(vec![(1, [5.0, 6.0])]));
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn gradient_round_trip() {
+        let mut graphic = lines();
+        graphic.gradients = vec![
+            Gradient {
+                shape: GradientShape::Linear {
+                    start: [0.0, 1.0],
+                    end: [2.0, 3.0],
+                },
+                stops: vec![(0.0, [1, 2, 3, 4]), (1.0, [5, 6, 7, 8])],
+                spread: Spread::Reflect,
+                transform: Affine2::new([1.0, 2.0, 3.0, 4.0, 5.0, 6.0]),
+            },
+            Gradient {
+                shape: GradientShape::Radial {
+                    center: [1.0, 1.0],
+                    radius: 2.0,
+                    focus: [0.5, 1.0],
+                },
+                stops: vec![],
+                spread: Spread::Pad,
+                transform: Affine2::IDENTITY,
+            },
+            Gradient {
+                shape: GradientShape::Conic {
+                    center: [4.0, 4.0],
+                    angle: 1.5,
+                },
+                stops: vec![(0.5, [9, 9, 9, 9])],
+                spread: Spread::Repeat,
+                transform: Affine2::IDENTITY,
+            },
+        ];
+        graphic.models[0].groups[0].1 = vec![GroupProperty::FillGradient(2)];
+        graphic.models[0].groups[1].1 = vec![GroupProperty::StrokeGradient(0)];
+        let copy = round_trip(&graphic);
+        assert_eq!(copy.gradients, graphic.gradients);
+        let model = &copy.models[0];
+        assert_eq!(model.fill_gradient(0), Some(2));
+        assert_eq!(model.stroke_gradient(0), None);
+        assert_eq!(model.stroke_gradient(1), Some(0));
+    }
+}
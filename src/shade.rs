@@ -0,0 +1,377 @@
+use crate::path::flatten_with;
+use crate::PathOp;
+
+/// Values blended across a filled path from the values of its vertices.
+///
+/// Each point of a flattened path is its position (x, y), followed by its
+/// `N - 2` values.  Each subpath is triangulated by ear clipping, and values
+/// are blended within each triangle by barycentric weights, with colors
+/// blended with premultiplied alpha.  Where the triangles of subpaths overlap, the later
+/// subpath wins.  Triangles are rasterized once, so that each pixel knows
+/// the triangle it is shaded by.
+pub(crate) struct Shading<const N: usize> {
+    triangles: Vec<[[f32; N]; 3]>,
+    /// Width of the raster the triangles are rasterized onto
+    width: usize,
+    /// How far outside (at most 0), and the index of, the triangle nearest
+    /// each pixel, for pixels within a pixel of any triangle
+    nearest: Vec<Option<(f32, u32)>>,
+}
+
+/// Get twice the signed area of a triangle.
+fn cross(a: &[f32], b: &[f32], c: &[f32]) -> f32 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+/// Get the barycentric weights of a position in a triangle, unless the
+/// triangle has no area.
+fn barycentric<const N: usize>(
+    [a, b, c]: &[[f32; N]; 3],
+    x: f32,
+    y: f32,
+) -> Option<[f32; 3]> {
+    let area = cross(a, b, c);
+    if area == 0.0 {
+        return None;
+    }
+    let p = [x, y];
+    let wa = cross(&p, b, c) / area;
+    let wb = cross(a, &p, c) / area;
+    Some([wa, wb, 1.0 - wa - wb])
+}
+
+/// Triangulate a polygon by ear clipping.  Polygons that intersect
+/// themselves, and have no ears left, are finished as a fan.
+fn triangulate<const N: usize>(
+    mut points: Vec<[f32; N]>,
+    triangles: &mut Vec<[[f32; N]; 3]>,
+) {
+    points.dedup_by(|a, b| a[0] == b[0] && a[1] == b[1]);
+    while points.len() > 1 {
+        let (first, last) = (points[0], points[points.len() - 1]);
+        if first[0] != last[0] || first[1] != last[1] {
+            break;
+        }
+        points.pop();
+    }
+    let n = points.len();
+    let area: f32 = (0..n)
+        .map(|i| {
+            let (a, b) = (&points[i], &points[(i + 1) % n]);
+            a[0] * b[1] - b[0] * a[1]
+        })
+        .sum();
+    if n < 3 || area == 0.0 {
+        return;
+    }
+    let sign = area.signum();
+    let mut indices: Vec<usize> = (0..n).collect();
+    while indices.len() > 3 {
+        let m = indices.len();
+        let corner = |i: usize| {
+            let a = &points[indices[(i + m - 1) % m]];
+            let b = &points[indices[i]];
+            let c = &points[indices[(i + 1) % m]];
+            (a, b, c, cross(a, b, c) * sign)
+        };
+        let ear = (0..m).find(|&i| {
+            let (a, b, c, turn) = corner(i);
+            turn > 0.0
+                && !indices.iter().map(|&j| &points[j]).any(|p| {
+                    let same = |q: &[f32; N]| p[0] == q[0] && p[1] == q[1];
+                    !same(a)
+                        && !same(b)
+                        && !same(c)
+                        && cross(a, b, p) * sign >= 0.0
+                        && cross(b, c, p) * sign >= 0.0
+                        && cross(c, a, p) * sign >= 0.0
+                })
+        });
+        if let Some(i) = ear {
+            let (a, b, c, _) = corner(i);
+            triangles.push([*a, *b, *c]);
+            indices.remove(i);
+        } else if let Some(i) = (0..m).find(|&i| corner(i).3 == 0.0) {
+            // A point on a straight line adds no area.
+            indices.remove(i);
+        } else {
+            break;
+        }
+    }
+    for k in 1..indices.len() - 1 {
+        let (a, b, c) = (indices[0], indices[k], indices[k + 1]);
+        triangles.push([points[a], points[b], points[c]]);
+    }
+}
+
+/// Get the range of x a triangle covers between two heights.
+fn span<const N: usize>(
+    triangle: &[[f32; N]; 3],
+    top: f32,
+    bottom: f32,
+) -> Option<(f32, f32)> {
+    let mut range: Option<(f32, f32)> = None;
+    let mut add = |x: f32| {
+        range = Some(range.map_or((x, x), |(l, r)| (l.min(x), r.max(x))));
+    };
+    for (i, a) in triangle.iter().enumerate() {
+        if a[1] >= top && a[1] <= bottom {
+            add(a[0]);
+        }
+        let b = &triangle[(i + 1) % 3];
+        for level in [top, bottom] {
+            if (a[1] - level) * (b[1] - level) < 0.0 {
+                add(a[0] + (b[0] - a[0]) * (level - a[1]) / (b[1] - a[1]));
+            }
+        }
+    }
+    range
+}
+
+impl<const N: usize> Shading<N> {
+    /// Shade a path onto a raster `width` by `height` pixels, with `vertex`
+    /// giving the position and values of each vertex.
+    pub(crate) fn new<F>(
+        ops: &[PathOp],
+        vertex: F,
+        (width, height): (u32, u32),
+    ) -> Option<Self>
+    where
+        F: Fn(u32) -> Option<[f32; N]>,
+    {
+        let mut triangles = Vec::new();
+        for (points, _closed) in flatten_with(ops, vertex)? {
+            triangulate(points, &mut triangles);
+        }
+        let (width, height) = (width as usize, height as usize);
+        let mut shading = Shading {
+            triangles,
+            width,
+            nearest: vec![None; width * height],
+        };
+        for index in 0..shading.triangles.len() {
+            shading.rasterize(index, height);
+        }
+        Some(shading)
+    }
+
+    /// Record a triangle as the nearest one to the pixels within a pixel of
+    /// it, where it is no further outside than the triangles before it.
+    fn rasterize(&mut self, index: usize, height: usize) {
+        let triangle = &self.triangles[index];
+        let (top, bottom) = triangle
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(top, bottom), p| {
+                (top.min(p[1]), bottom.max(p[1]))
+            });
+        if !(top.is_finite() && bottom.is_finite()) {
+            return;
+        }
+        let first = (top - 1.0).floor().max(0.0) as usize;
+        let last = ((bottom + 1.0).ceil().max(0.0) as usize).min(height);
+        for y in first..last {
+            let center = y as f32 + 0.5;
+            let (left, right) = match span(triangle, center - 1.0, center + 1.0)
+            {
+                Some(span) => span,
+                None => continue,
+            };
+            let left = (left - 1.0).floor().max(0.0) as usize;
+            let right =
+                ((right + 1.0).ceil().max(0.0) as usize).min(self.width);
+            for x in left..right {
+                let weights =
+                    match barycentric(triangle, x as f32 + 0.5, center) {
+                        Some(weights) => weights,
+                        None => return,
+                    };
+                let outside = weights.iter().cloned().fold(0.0, f32::min);
+                let nearest = &mut self.nearest[y * self.width + x];
+                let closer = match *nearest {
+                    Some((most, _)) => outside >= most,
+                    None => true,
+                };
+                if closer {
+                    *nearest = Some((outside, index as u32));
+                }
+            }
+        }
+    }
+
+    /// Get the triangle at a position, and the barycentric weights of the
+    /// position in it.  Outside of every triangle, it is the nearest point on
+    /// the edge of the triangle it is least outside of.
+    fn nearest(&self, x: f32, y: f32) -> Option<([f32; 3], &[[f32; N]; 3])> {
+        if x < 0.0 || y < 0.0 || x as usize >= self.width {
+            return None;
+        }
+        let pixel = y as usize * self.width + x as usize;
+        let (_, index) = (*self.nearest.get(pixel)?)?;
+        let triangle = &self.triangles[index as usize];
+        let weights = barycentric(triangle, x, y)?.map(|w| w.max(0.0));
+        let total: f32 = weights.iter().sum();
+        Some((weights.map(|weight| weight / total), triangle))
+    }
+
+    /// Get the point at a position, with its values blended linearly.
+    pub(crate) fn point(&self, x: f32, y: f32) -> Option<[f32; N]> {
+        let (weights, triangle) = self.nearest(x, y)?;
+        let mut point = [0.0; N];
+        for (weight, corner) in weights.iter().zip(triangle) {
+            for (sum, value) in point.iter_mut().zip(corner) {
+                *sum += weight * value;
+            }
+        }
+        Some(point)
+    }
+}
+
+impl Shading<6> {
+    /// Get the sRGBA color at a position, where the values of each point are
+    /// its sRGBA color (0 to 1).
+    pub(crate) fn color(&self, x: f32, y: f32) -> [u8; 4] {
+        let (weights, triangle) = match self.nearest(x, y) {
+            Some(nearest) => nearest,
+            None => return [0, 0, 0, 0],
+        };
+        let mut alpha = 0.0;
+        let mut rgb = [0.0; 3];
+        for (weight, point) in weights.iter().zip(triangle) {
+            let a = point[5].clamp(0.0, 1.0);
+            alpha += weight * a;
+            for (sum, value) in rgb.iter_mut().zip(&point[2..5]) {
+                *sum += weight * a * value.clamp(0.0, 1.0);
+            }
+        }
+        let byte = |value: f32| (value * 255.0).round() as u8;
+        if alpha > 0.0 {
+            let [r, g, b] = rgb.map(|value| byte(value / alpha));
+            [r, g, b, byte(alpha)]
+        } else {
+            [0, 0, 0, 0]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Triangulate a polygon of positions.
+    fn triangles(points: &[[f32; 2]]) -> Vec<[[f32; 2]; 3]> {
+        let mut triangles = Vec::new();
+        triangulate(points.to_vec(), &mut triangles);
+        triangles
+    }
+
+    /// Get the total area of triangles.
+    fn area(triangles: &[[[f32; 2]; 3]]) -> f32 {
+        triangles
+            .iter()
+            .map(|[a, b, c]| cross(a, b, c).abs() / 2.0)
+            .sum()
+    }
+
+    /// Get a closed path through each vertex in order.
+    fn polygon(n: u32) -> Vec<PathOp> {
+        let mut ops = vec![PathOp::Move(0)];
+        ops.extend((1..n).map(PathOp::Line));
+        ops.push(PathOp::Close());
+        ops
+    }
+
+    #[test]
+    fn triangulate_convex() {
+        let square = [[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0]];
+        let triangles = triangles(&square);
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(area(&triangles), 16.0);
+        // Either winding gives the same area.
+        let mut reversed = square;
+        reversed.reverse();
+        assert_eq!(area(&self::triangles(&reversed)), 16.0);
+    }
+
+    #[test]
+    fn triangulate_concave() {
+        let l = [
+            [0.0, 0.0],
+            [4.0, 0.0],
+            [4.0, 2.0],
+            [2.0, 2.0],
+            [2.0, 4.0],
+            [0.0, 4.0],
+        ];
+        let triangles = triangles(&l);
+        assert_eq!(triangles.len(), 4);
+        assert_eq!(area(&triangles), 12.0);
+        // No triangle covers the missing corner.
+        for triangle in &triangles {
+            let weights = barycentric(triangle, 3.0, 3.0).unwrap();
+            assert!(weights.iter().any(|w| *w < 0.0), "{:?}", triangle);
+        }
+    }
+
+    #[test]
+    fn triangulate_degenerate() {
+        // A point on a straight line, and a repeated closing point.
+        let square = [
+            [0.0, 0.0],
+            [2.0, 0.0],
+            [4.0, 0.0],
+            [4.0, 4.0],
+            [0.0, 4.0],
+            [0.0, 0.0],
+        ];
+        let triangles = triangles(&square);
+        assert_eq!(area(&triangles), 16.0);
+        assert!(triangles.iter().all(|[a, b, c]| cross(a, b, c) != 0.0));
+        assert!(
+            self::triangles(&[[0.0, 0.0], [1.0, 1.0], [2.0, 2.0]]).is_empty()
+        );
+        assert!(self::triangles(&[[0.0, 0.0], [1.0, 1.0]]).is_empty());
+    }
+
+    #[test]
+    fn triangulate_self_intersecting() {
+        // A lopsided bow tie is still covered by triangles.
+        let bow = [[0.0, 0.0], [4.0, 4.0], [4.0, 0.0], [0.0, 6.0]];
+        assert_eq!(triangles(&bow).len(), 2);
+        // One with no area has nothing to shade.
+        let bow = [[0.0, 0.0], [4.0, 4.0], [4.0, 0.0], [0.0, 4.0]];
+        assert!(triangles(&bow).is_empty());
+    }
+
+    #[test]
+    fn shade_colors() {
+        // Opaque red on the left, and transparent blue on the right.
+        let vertices = [
+            [0.0, 0.0, 1.0, 0.0, 0.0, 1.0],
+            [4.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+            [4.0, 4.0, 0.0, 0.0, 1.0, 0.0],
+            [0.0, 4.0, 1.0, 0.0, 0.0, 1.0],
+        ];
+        let vertex = |i: u32| vertices.get(i as usize).copied();
+        let shading = Shading::new(&polygon(4), vertex, (8, 8)).unwrap();
+        // Premultiplied, so the color doesn't fade to blue with alpha.
+        assert_eq!(shading.color(0.5, 2.5), [255, 0, 0, 223]);
+        assert_eq!(shading.color(2.5, 1.5), [255, 0, 0, 96]);
+        assert_eq!(shading.color(2.5, 2.5), [255, 0, 0, 96]);
+        // Just outside, the nearest edge is used.
+        assert_eq!(shading.color(4.5, 2.5), [0, 0, 0, 0]);
+        let [r, g, b, a] = shading.color(2.5, 4.5);
+        assert_eq!([r, g, b], [255, 0, 0]);
+        assert!(a > 0 && a < 128, "{}", a);
+        // Far outside, and off the raster, is clear.
+        assert_eq!(shading.color(7.5, 7.5), [0, 0, 0, 0]);
+        assert_eq!(shading.color(-1.0, 2.5), [0, 0, 0, 0]);
+        assert_eq!(shading.color(2.5, 9.0), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn shade_missing_vertex() {
+        let vertices = [[0.0, 0.0, 1.0, 1.0, 1.0, 1.0]];
+        let vertex = |i: u32| vertices.get(i as usize).copied();
+        assert!(Shading::new(&polygon(3), vertex, (8, 8)).is_none());
+    }
+}
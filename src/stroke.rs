@@ -0,0 +1,350 @@
+use crate::path::{dash_with, flatten_with};
+use crate::{JoinStyle, LineCap, PathOp};
+use footile::Path2D;
+
+/// A point of a flattened path: its position (x, y), and the width of the
+/// stroke there.
+type Point = [f32; 3];
+
+/// Get the unit vector from `a` to `b`, if they are different points.
+fn direction(a: &Point, b: &Point) -> Option<(f32, f32)> {
+    let (x, y) = (b[0] - a[0], b[1] - a[1]);
+    let len = x.hypot(y);
+    if len > 0.0 {
+        Some((x / len, y / len))
+    } else {
+        None
+    }
+}
+
+/// Add a polygon to a path, wound the same way as every other shape of the
+/// outline so that overlaps don't cancel out.  Polygons without area are
+/// left out.
+fn polygon(path: Path2D, points: &[(f32, f32)]) -> Path2D {
+    let n = points.len();
+    let area: f32 = (0..n)
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % n]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum();
+    if area == 0.0 || area.is_nan() {
+        return path;
+    }
+    let mut points: Vec<(f32, f32)> = points.to_vec();
+    if area < 0.0 {
+        points.reverse();
+    }
+    let (x, y) = points[0];
+    let mut path = path.move_to(x, y);
+    for &(x, y) in &points[1..] {
+        path = path.line_to(x, y);
+    }
+    path.close()
+}
+
+/// Add a circle, from four cubic curves, to a path.
+fn circle(path: Path2D, (x, y): (f32, f32), r: f32) -> Path2D {
+    if r.is_nan() || r <= 0.0 {
+        return path;
+    }
+    let k = 0.552_284_8 * r;
+    path.move_to(x + r, y)
+        .cubic_to(x + r, y + k, x + k, y + r, x, y + r)
+        .cubic_to(x - k, y + r, x - r, y + k, x - r, y)
+        .cubic_to(x - r, y - k, x - k, y - r, x, y - r)
+        .cubic_to(x + k, y - r, x + r, y - k, x + r, y)
+        .close()
+}
+
+/// Add a line cap at the end `p` of a stroke, pointing out in direction `d`.
+fn cap(path: Path2D, cap: LineCap, p: &Point, (dx, dy): (f32, f32)) -> Path2D {
+    let r = p[2] / 2.0;
+    match cap {
+        LineCap::Butt => path,
+        LineCap::Round => circle(path, (p[0], p[1]), r),
+        LineCap::Square => {
+            let (nx, ny) = (-dy * r, dx * r);
+            let (fx, fy) = (p[0] + dx * r, p[1] + dy * r);
+            polygon(
+                path,
+                &[
+                    (p[0] + nx, p[1] + ny),
+                    (fx + nx, fy + ny),
+                    (fx - nx, fy - ny),
+                    (p[0] - nx, p[1] - ny),
+                ],
+            )
+        }
+    }
+}
+
+/// Add a join at `p`, between a segment coming in direction `d0` and one
+/// going out in direction `d1`.
+fn join(
+    path: Path2D,
+    (style, miter_limit): (JoinStyle, f32),
+    p: &Point,
+    d0: (f32, f32),
+    d1: (f32, f32),
+) -> Path2D {
+    let r = p[2] / 2.0;
+    let cross = d0.0 * d1.1 - d0.1 * d1.0;
+    let dot = d0.0 * d1.0 + d0.1 * d1.1;
+    if cross == 0.0 && dot > 0.0 {
+        return path;
+    }
+    if style == JoinStyle::Round {
+        return circle(path, (p[0], p[1]), r);
+    }
+    // The join fills the gap on the outside of the turn.
+    let side = if cross > 0.0 { -r } else { r };
+    let (n0, n1) = ((-d0.1 * side, d0.0 * side), (-d1.1 * side, d1.0 * side));
+    let o0 = (p[0] + n0.0, p[1] + n0.1);
+    let o1 = (p[0] + n1.0, p[1] + n1.1);
+    let ratio = (2.0 / (1.0 + dot)).sqrt();
+    if style == JoinStyle::Miter && ratio <= miter_limit {
+        let (mx, my) = (
+            p[0] + (n0.0 + n1.0) / (1.0 + dot),
+            p[1] + (n0.1 + n1.1) / (1.0 + dot),
+        );
+        polygon(path, &[(p[0], p[1]), o0, (mx, my), o1])
+    } else {
+        polygon(path, &[(p[0], p[1]), o0, o1])
+    }
+}
+
+/// Add the outline of a stroke along flattened points to a path.
+fn stroke(
+    mut path: Path2D,
+    mut points: Vec<Point>,
+    closed: bool,
+    (style, miter_limit, line_cap): (JoinStyle, f32, LineCap),
+) -> Path2D {
+    points.dedup_by(|a, b| a[0] == b[0] && a[1] == b[1]);
+    if closed && points.len() > 1 {
+        let (first, last) = (points[0], points[points.len() - 1]);
+        if first[0] == last[0] && first[1] == last[1] {
+            points.pop();
+        }
+    }
+    for point in points.iter_mut() {
+        point[2] = point[2].max(0.0);
+    }
+    let n = points.len();
+    match n {
+        0 => return path,
+        1 if closed => return path,
+        // Like SVG, a subpath without length is capped facing right.
+        1 => {
+            let p = &points[0];
+            path = self::cap(path, line_cap, p, (-1.0, 0.0));
+            return self::cap(path, line_cap, p, (1.0, 0.0));
+        }
+        _ => {}
+    }
+
+    // Each segment, with the widths at its ends.
+    let segments = if closed { n } else { n - 1 };
+    let mut directions = Vec::with_capacity(segments);
+    for i in 0..segments {
+        let (a, b) = (&points[i], &points[(i + 1) % n]);
+        let d = direction(a, b).unwrap_or((1.0, 0.0));
+        let (ra, rb) = (a[2] / 2.0, b[2] / 2.0);
+        let (nx, ny) = (-d.1, d.0);
+        path = polygon(
+            path,
+            &[
+                (a[0] + nx * ra, a[1] + ny * ra),
+                (b[0] + nx * rb, b[1] + ny * rb),
+                (b[0] - nx * rb, b[1] - ny * rb),
+                (a[0] - nx * ra, a[1] - ny * ra),
+            ],
+        );
+        directions.push(d);
+    }
+
+    // Joins between segments, at the width where they meet.
+    let joins = if closed { 0..n } else { 1..n - 1 };
+    for i in joins {
+        let d0 = directions[(i + segments - 1) % segments];
+        path = join(path, (style, miter_limit), &points[i], d0, directions[i]);
+    }
+
+    if !closed {
+        let (sx, sy) = directions[0];
+        path = self::cap(path, line_cap, &points[0], (-sx, -sy));
+        let end = directions[segments - 1];
+        path = self::cap(path, line_cap, &points[n - 1], end);
+    }
+    path
+}
+
+/// Build the outline of a stroke whose width varies along a path, to fill
+/// with the non-zero rule.  `vertex` gives the position of each vertex and
+/// the width of the stroke there, which is interpolated along each segment,
+/// and the joins and caps are as wide as the stroke where they are.  Returns
+/// `None` if `vertex` doesn't give every vertex of the path.
+pub(crate) fn outline<F>(
+    ops: &[PathOp],
+    vertex: F,
+    (style, miter_limit, line_cap): (JoinStyle, f32, LineCap),
+    dash: Option<(&[f32], f32)>,
+) -> Option<Vec<footile::PathOp>>
+where
+    F: Fn(u32) -> Option<Point>,
+{
+    let mut path = Path2D::default().absolute();
+    for (points, closed) in flatten_with(ops, vertex)? {
+        let pieces = match dash {
+            Some((pattern, offset)) => {
+                dash_with(&points, closed, pattern, offset)
+            }
+            None => vec![(points, closed)],
+        };
+        for (points, closed) in pieces {
+            let styles = (style, miter_limit, line_cap);
+            path = stroke(path, points, closed, styles);
+        }
+    }
+    Some(path.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use footile::{FillRule, Plotter};
+    use pix::{el::Pixel, matte::Matte8, Raster};
+
+    /// Styles with butt caps and miter joins.
+    const BUTT: (JoinStyle, f32, LineCap) =
+        (JoinStyle::Miter, 4.0, LineCap::Butt);
+
+    /// Stroke a path through points, with the width of the stroke at each,
+    /// and fill its outline onto a 16 × 16 matte.
+    fn fill(
+        points: &[Point],
+        ops: &[PathOp],
+        styles: (JoinStyle, f32, LineCap),
+        dash: Option<(&[f32], f32)>,
+    ) -> Raster<Matte8> {
+        let vertex = |i: u32| points.get(i as usize).copied();
+        let outline = outline(ops, vertex, styles, dash).unwrap();
+        let mut plotter = Plotter::new(Raster::with_clear(16, 16));
+        plotter.fill(FillRule::NonZero, &outline, Matte8::new(255));
+        plotter.raster()
+    }
+
+    /// Get a path through each vertex in order.
+    fn line(n: u32) -> Vec<PathOp> {
+        let mut ops = vec![PathOp::Move(0)];
+        ops.extend((1..n).map(PathOp::Line));
+        ops
+    }
+
+    /// Count the pixels of a column at least half covered.
+    fn column(matte: &Raster<Matte8>, x: i32) -> usize {
+        (0..16)
+            .filter(|&y| u8::from(matte.pixel(x, y).alpha()) >= 128)
+            .count()
+    }
+
+    /// Get the coverage of a pixel.
+    fn alpha(matte: &Raster<Matte8>, x: i32, y: i32) -> u8 {
+        u8::from(matte.pixel(x, y).alpha())
+    }
+
+    #[test]
+    fn stroke_tapers() {
+        let points = [[2.0, 8.0, 2.0], [14.0, 8.0, 6.0]];
+        let matte = fill(&points, &line(2), BUTT, None);
+        assert_eq!(column(&matte, 2), 2);
+        assert_eq!(column(&matte, 8), 4);
+        assert_eq!(column(&matte, 13), 6);
+        assert_eq!(column(&matte, 1), 0);
+        assert_eq!(column(&matte, 14), 0);
+    }
+
+    #[test]
+    fn stroke_caps() {
+        let points = [[4.0, 8.0, 4.0], [12.0, 8.0, 4.0]];
+        let caps = |cap| {
+            let matte =
+                fill(&points, &line(2), (JoinStyle::Miter, 4.0, cap), None);
+            [3, 2, 1, 12, 13].map(|x| column(&matte, x))
+        };
+        assert_eq!(caps(LineCap::Butt), [0, 0, 0, 0, 0]);
+        // Square caps reach half the width past each end.
+        assert_eq!(caps(LineCap::Square), [4, 4, 0, 4, 4]);
+        // Round caps narrow as they go.
+        assert_eq!(caps(LineCap::Round), [4, 2, 0, 4, 2]);
+    }
+
+    #[test]
+    fn stroke_dot() {
+        // A subpath without length is only its caps.
+        let points = [[8.0, 8.0, 4.0], [8.0, 8.0, 4.0]];
+        let matte = fill(&points, &line(2), BUTT, None);
+        assert_eq!(alpha(&matte, 8, 8), 0);
+        let square = (JoinStyle::Miter, 4.0, LineCap::Square);
+        let matte = fill(&points, &line(2), square, None);
+        assert_eq!(column(&matte, 6), 4);
+        assert_eq!(column(&matte, 9), 4);
+        assert_eq!(column(&matte, 10), 0);
+    }
+
+    #[test]
+    fn stroke_joins() {
+        // Right angle, bending down at (12, 4).
+        let points = [[2.0, 4.0, 4.0], [12.0, 4.0, 4.0], [12.0, 14.0, 4.0]];
+        let corner = |style| {
+            let matte =
+                fill(&points, &line(3), (style, 4.0, LineCap::Butt), None);
+            alpha(&matte, 13, 2)
+        };
+        assert_eq!(corner(JoinStyle::Miter), 255);
+        assert_eq!(corner(JoinStyle::Bevel), 0);
+        assert!(corner(JoinStyle::Round) > 0);
+        // A miter longer than its limit is beveled.
+        let matte = fill(
+            &points,
+            &line(3),
+            (JoinStyle::Miter, 1.0, LineCap::Butt),
+            None,
+        );
+        assert_eq!(alpha(&matte, 13, 2), 0);
+    }
+
+    #[test]
+    fn stroke_closed() {
+        let points = [
+            [4.0, 4.0, 2.0],
+            [12.0, 4.0, 2.0],
+            [12.0, 12.0, 2.0],
+            [4.0, 12.0, 2.0],
+        ];
+        let mut ops = line(4);
+        ops.push(PathOp::Close());
+        let matte = fill(&points, &ops, BUTT, None);
+        // Joined at every corner, and hollow in the middle.
+        assert_eq!(alpha(&matte, 3, 3), 255);
+        assert_eq!(alpha(&matte, 12, 12), 255);
+        assert_eq!(alpha(&matte, 8, 8), 0);
+        assert_eq!(column(&matte, 8), 4);
+    }
+
+    #[test]
+    fn stroke_dashes() {
+        let points = [[0.0, 8.0, 2.0], [16.0, 8.0, 2.0]];
+        let matte = fill(&points, &line(2), BUTT, Some((&[2.0, 2.0], 0.0)));
+        let row: Vec<u8> = (0..8).map(|x| alpha(&matte, x, 8)).collect();
+        assert_eq!(row, vec![255, 255, 0, 0, 255, 255, 0, 0]);
+    }
+
+    #[test]
+    fn outline_missing_vertex() {
+        let points = [[2.0, 8.0, 2.0]];
+        let vertex = |i: u32| points.get(i as usize).copied();
+        assert!(outline(&line(2), vertex, BUTT, None).is_none());
+    }
+}
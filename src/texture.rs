@@ -0,0 +1,145 @@
+use crate::{Bitmap, Sampling, Wrap};
+
+impl Wrap {
+    /// Get the index of a pixel in a row or column of `len` pixels.
+    fn index(self, i: i64, len: i64) -> usize {
+        match self {
+            Wrap::Repeat => i.rem_euclid(len) as usize,
+            Wrap::Clamp => i.clamp(0, len - 1) as usize,
+        }
+    }
+}
+
+impl Bitmap {
+    /// Get the sRGBA color at a position, in pixels from the top left
+    /// corner.  Bitmaps without every pixel are clear.
+    pub fn sample(
+        &self,
+        x: f32,
+        y: f32,
+        sampling: Sampling,
+        wrap: Wrap,
+    ) -> [u8; 4] {
+        let (width, height) = (i64::from(self.width), i64::from(self.height));
+        if width == 0
+            || height == 0
+            || self.srgba.len() < (width * height * 4) as usize
+        {
+            return [0, 0, 0, 0];
+        }
+        let pixel = |x: i64, y: i64| {
+            let i = (wrap.index(y, height) * width as usize
+                + wrap.index(x, width))
+                * 4;
+            [
+                self.srgba[i],
+                self.srgba[i + 1],
+                self.srgba[i + 2],
+                self.srgba[i + 3],
+            ]
+        };
+        match sampling {
+            Sampling::Nearest => pixel(x.floor() as i64, y.floor() as i64),
+            Sampling::Bilinear => {
+                // Blend the pixels with centers around the position.
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (left, top) = (x.floor(), y.floor());
+                let (tx, ty) = (x - left, y - top);
+                let (left, top) = (left as i64, top as i64);
+                let corners = [
+                    (pixel(left, top), (1.0 - tx) * (1.0 - ty)),
+                    (pixel(left + 1, top), tx * (1.0 - ty)),
+                    (pixel(left, top + 1), (1.0 - tx) * ty),
+                    (pixel(left + 1, top + 1), tx * ty),
+                ];
+                let mut alpha = 0.0;
+                let mut rgb = [0.0; 3];
+                for (color, weight) in &corners {
+                    let a = f32::from(color[3]) * weight;
+                    alpha += a;
+                    for (sum, value) in rgb.iter_mut().zip(color) {
+                        *sum += f32::from(*value) * a;
+                    }
+                }
+                if alpha > 0.0 {
+                    let [r, g, b] = rgb.map(|sum| (sum / alpha).round() as u8);
+                    [r, g, b, alpha.round() as u8]
+                } else {
+                    [0, 0, 0, 0]
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 2 × 2 bitmap: opaque red, green on top, and opaque blue, clear
+    /// below.
+    fn bitmap() -> Bitmap {
+        Bitmap {
+            width: 2,
+            height: 2,
+            srgba: vec![
+                255, 0, 0, 255, 0, 255, 0, 255, //
+                0, 0, 255, 255, 0, 0, 0, 0, //
+            ],
+        }
+    }
+
+    #[test]
+    fn wrap_index() {
+        assert_eq!(Wrap::Repeat.index(-1, 3), 2);
+        assert_eq!(Wrap::Repeat.index(4, 3), 1);
+        assert_eq!(Wrap::Clamp.index(-1, 3), 0);
+        assert_eq!(Wrap::Clamp.index(4, 3), 2);
+        assert_eq!(Wrap::Clamp.index(1, 3), 1);
+    }
+
+    #[test]
+    fn sample_nearest() {
+        let bitmap = bitmap();
+        let sample = |x, y, wrap| bitmap.sample(x, y, Sampling::Nearest, wrap);
+        assert_eq!(sample(0.5, 0.5, Wrap::Repeat), [255, 0, 0, 255]);
+        assert_eq!(sample(1.9, 0.1, Wrap::Repeat), [0, 255, 0, 255]);
+        assert_eq!(sample(0.0, 1.0, Wrap::Repeat), [0, 0, 255, 255]);
+        // Off the right edge, repeating wraps back to the left.
+        assert_eq!(sample(2.5, 0.5, Wrap::Repeat), [255, 0, 0, 255]);
+        assert_eq!(sample(-0.5, 0.5, Wrap::Repeat), [0, 255, 0, 255]);
+        // Clamping extends the edge pixels.
+        assert_eq!(sample(2.5, 0.5, Wrap::Clamp), [0, 255, 0, 255]);
+        assert_eq!(sample(-7.0, 9.0, Wrap::Clamp), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn sample_bilinear() {
+        let bitmap = bitmap();
+        let sample = |x, y, wrap| bitmap.sample(x, y, Sampling::Bilinear, wrap);
+        // At the center of a pixel, only that pixel is seen.
+        assert_eq!(sample(0.5, 0.5, Wrap::Clamp), [255, 0, 0, 255]);
+        // Halfway between red and green.
+        assert_eq!(sample(1.0, 0.5, Wrap::Clamp), [128, 128, 0, 255]);
+        // Blended with premultiplied alpha, so the clear pixel has no color.
+        assert_eq!(sample(1.0, 1.5, Wrap::Clamp), [0, 0, 255, 128]);
+        // Halfway across the wrapped edge, between green and red.
+        assert_eq!(sample(0.0, 0.5, Wrap::Repeat), [128, 128, 0, 255]);
+        assert_eq!(sample(0.0, 0.5, Wrap::Clamp), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn sample_empty() {
+        let mut bitmap = bitmap();
+        bitmap.srgba.pop();
+        let sampled = bitmap.sample(0.5, 0.5, Sampling::Nearest, Wrap::Clamp);
+        assert_eq!(sampled, [0, 0, 0, 0]);
+        let empty = Bitmap {
+            width: 0,
+            height: 0,
+            srgba: vec![],
+        };
+        let sampled = empty.sample(0.5, 0.5, Sampling::Bilinear, Wrap::Repeat);
+        assert_eq!(sampled, [0, 0, 0, 0]);
+    }
+}
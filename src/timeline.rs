@@ -0,0 +1,261 @@
+use crate::{Animation, Frame, Model, StepPosition};
+
+impl Animation {
+    /// Get the animation that eases the same way when played backwards.
+    pub fn reversed(self) -> Self {
+        match self {
+            Animation::CubicBezier(x1, y1, x2, y2) => {
+                Animation::CubicBezier(1.0 - x2, 1.0 - y2, 1.0 - x1, 1.0 - y1)
+            }
+            Animation::Steps(steps, position) => Animation::Steps(
+                steps,
+                match position {
+                    StepPosition::JumpStart => StepPosition::JumpEnd,
+                    StepPosition::JumpEnd => StepPosition::JumpStart,
+                    position => position,
+                },
+            ),
+            animation => animation,
+        }
+    }
+}
+
+/// Editing keyframes.
+///
+/// These keep `Animation::Done` on the last frame, and only on the last
+/// frame: a frame that stops being the last one gets `Animation::Jump`.
+/// Methods return `None`, and leave the model as it was, if an index is out
+/// of range or a frame doesn't have a transform list for each group.
+impl Model {
+    /// Check that a frame fits the model's groups.
+    fn fits(&self, frame: &Frame) -> bool {
+        frame.transforms.len() == self.groups.len()
+            && frame.properties.len() <= self.groups.len()
+    }
+
+    /// Check that there is at least one frame, every frame has a transform
+    /// list for each group, and only the last frame is `Animation::Done`.
+    pub fn is_valid(&self) -> bool {
+        let last = self.frames.len().max(1) - 1;
+        !self.frames.is_empty()
+            && self.frames.iter().enumerate().all(|(i, frame)| {
+                self.fits(frame)
+                    && (i == last) == (frame.animation == Animation::Done)
+            })
+    }
+
+    /// Put `Animation::Done` on the last frame, and only the last frame.
+    fn terminate(&mut self) {
+        let last = self.frames.len().max(1) - 1;
+        for (i, frame) in self.frames.iter_mut().enumerate() {
+            if i == last {
+                frame.animation = Animation::Done;
+            } else if frame.animation == Animation::Done {
+                frame.animation = Animation::Jump;
+            }
+        }
+    }
+
+    /// Insert a keyframe before the frame at `index`, or at the end if
+    /// `index` is the number of frames.
+    pub fn insert_frame(&mut self, index: usize, frame: Frame) -> Option<()> {
+        if index > self.frames.len() || !self.fits(&frame) {
+            return None;
+        }
+        self.frames.insert(index, frame);
+        self.terminate();
+        Some(())
+    }
+
+    /// Remove a keyframe, and return it.  The only frame can't be removed.
+    pub fn remove_frame(&mut self, index: usize) -> Option<Frame> {
+        if index >= self.frames.len() || self.frames.len() == 1 {
+            return None;
+        }
+        let frame = self.frames.remove(index);
+        self.terminate();
+        Some(frame)
+    }
+
+    /// Insert a copy of a keyframe after it.
+    pub fn duplicate_frame(&mut self, index: usize) -> Option<()> {
+        let frame = self.frames.get(index)?.clone();
+        self.insert_frame(index + 1, frame)
+    }
+
+    /// Set the milliseconds from a keyframe until the next one.
+    pub fn set_delay(&mut self, index: usize, delay: u16) -> Option<()> {
+        self.frames.get_mut(index)?.delay = delay;
+        Some(())
+    }
+
+    /// Set how a keyframe transitions into the next one.  The last frame is
+    /// always `Animation::Done`, and no other frame can be.
+    pub fn set_animation(
+        &mut self,
+        index: usize,
+        animation: Animation,
+    ) -> Option<()> {
+        let last = index + 1 == self.frames.len();
+        if last != (animation == Animation::Done) {
+            return None;
+        }
+        self.frames.get_mut(index)?.animation = animation;
+        Some(())
+    }
+
+    /// Reverse the order of the keyframes, moving each transition to the
+    /// frame it now starts from, so the timeline plays backwards.
+    pub fn reverse_frames(&mut self) {
+        let transitions: Vec<(u16, Animation)> = self
+            .frames
+            .iter()
+            .map(|frame| (frame.delay, frame.animation.reversed()))
+            .collect();
+        let count = self.frames.len();
+        self.frames.reverse();
+        for (i, frame) in self.frames.iter_mut().enumerate() {
+            if i + 1 < count {
+                let (delay, animation) = transitions[count - 2 - i];
+                frame.delay = delay;
+                frame.animation = animation;
+            } else {
+                frame.delay = transitions[count - 1].0;
+            }
+        }
+        self.terminate();
+    }
+
+    /// Scale the delays of every keyframe, and the playback duration, by a
+    /// factor.  Delays are rounded to the nearest millisecond, and clamped
+    /// to the range of `u16`.
+    pub fn scale_timeline(&mut self, factor: f32) {
+        for frame in self.frames.iter_mut() {
+            let delay = (f32::from(frame.delay) * factor).round();
+            frame.delay = delay.clamp(0.0, f32::from(u16::MAX)) as u16;
+        }
+        let duration = (self.playback.duration as f32 * factor).round();
+        self.playback.duration = duration.clamp(0.0, u32::MAX as f32) as u32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Morph, TransformOp};
+
+    /// Make a keyframe of one group, told apart by how far it translates.
+    fn frame(x: f32, delay: u16, animation: Animation) -> Frame {
+        Frame {
+            transforms: vec![vec![TransformOp::Translate(x, 0.0, 0.0)]],
+            properties: Vec::new(),
+            morph: Morph::None,
+            delay,
+            animation,
+        }
+    }
+
+    fn model(frames: Vec<Frame>) -> Model {
+        Model {
+            frames,
+            groups: vec![(0, Vec::new())],
+            width: 1.0,
+            height: 1.0,
+            playback: Default::default(),
+        }
+    }
+
+    /// Get the translation, delay and animation of each frame.
+    fn frames(model: &Model) -> Vec<(f32, u16, Animation)> {
+        model
+            .frames
+            .iter()
+            .map(|frame| match frame.transforms[0][0] {
+                TransformOp::Translate(x, _, _) => {
+                    (x, frame.delay, frame.animation)
+                }
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn insert_frame_at_end() {
+        let mut m = model(vec![frame(0.0, 10, Animation::Done)]);
+        m.insert_frame(1, frame(1.0, 20, Animation::Done)).unwrap();
+        assert!(m.is_valid());
+        assert_eq!(
+            frames(&m),
+            [(0.0, 10, Animation::Jump), (1.0, 20, Animation::Done)]
+        );
+        assert!(m.insert_frame(3, frame(2.0, 0, Animation::Done)).is_none());
+    }
+
+    #[test]
+    fn duplicate_last_frame() {
+        let mut m = model(vec![
+            frame(0.0, 10, Animation::Linear),
+            frame(1.0, 20, Animation::Done),
+        ]);
+        m.duplicate_frame(1).unwrap();
+        assert!(m.is_valid());
+        assert_eq!(
+            frames(&m),
+            [
+                (0.0, 10, Animation::Linear),
+                (1.0, 20, Animation::Jump),
+                (1.0, 20, Animation::Done),
+            ]
+        );
+    }
+
+    #[test]
+    fn remove_last_frame() {
+        let mut m = model(vec![
+            frame(0.0, 10, Animation::Linear),
+            frame(1.0, 20, Animation::Done),
+        ]);
+        let removed = m.remove_frame(1).unwrap();
+        assert_eq!(removed.delay, 20);
+        assert!(m.is_valid());
+        assert_eq!(frames(&m), [(0.0, 10, Animation::Done)]);
+        assert!(m.remove_frame(0).is_none());
+    }
+
+    #[test]
+    fn reverse_one_frame() {
+        let mut m = model(vec![frame(0.0, 10, Animation::Done)]);
+        m.reverse_frames();
+        assert!(m.is_valid());
+        assert_eq!(frames(&m), [(0.0, 10, Animation::Done)]);
+    }
+
+    #[test]
+    fn reverse_three_frames() {
+        let ease = Animation::CubicBezier(0.25, 0.5, 0.125, 0.75);
+        let mut m = model(vec![
+            frame(0.0, 10, Animation::Linear),
+            frame(1.0, 20, ease),
+            frame(2.0, 30, Animation::Done),
+        ]);
+        m.reverse_frames();
+        assert!(m.is_valid());
+        assert_eq!(
+            frames(&m),
+            [
+                (2.0, 20, ease.reversed()),
+                (1.0, 10, Animation::Linear),
+                (0.0, 30, Animation::Done),
+            ]
+        );
+        m.reverse_frames();
+        assert_eq!(
+            frames(&m),
+            [
+                (0.0, 10, Animation::Linear),
+                (1.0, 20, ease),
+                (2.0, 30, Animation::Done),
+            ]
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use crate::Clip;
+use pix::{rgb::SRgba8, Raster};
+use std::io::Write;
+
+/// How many pixels share each pair of chroma (Cb, Cr) samples.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Subsampling {
+    /// Every pixel has its own chroma
+    C444,
+    /// Each pair of pixels in a row shares chroma
+    C422,
+    /// Each 2×2 block of pixels shares chroma
+    C420,
+}
+
+/// Settings for YUV4MPEG2 export.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Y4mSettings {
+    /// Chroma subsampling
+    pub subsampling: Subsampling,
+    /// Color (sRGB) that transparent pixels are composited over
+    pub background: [u8; 3],
+}
+
+impl Default for Y4mSettings {
+    fn default() -> Self {
+        Y4mSettings {
+            subsampling: Subsampling::C420,
+            background: [0, 0, 0],
+        }
+    }
+}
+
+/// Get the greatest common divisor of two numbers.
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Convert a frame to BT.709 limited range Y′, Cb and Cr planes, at full
+/// resolution.
+fn planes(raster: &Raster<SRgba8>, background: [u8; 3]) -> [Vec<f32>; 3] {
+    let len = (raster.width() * raster.height()) as usize;
+    let mut planes = [
+        Vec::with_capacity(len),
+        Vec::with_capacity(len),
+        Vec::with_capacity(len),
+    ];
+    for pixel in raster.as_u8_slice().chunks_exact(4) {
+        let alpha = f32::from(pixel[3]) / 255.0;
+        let mut rgb = [0.0; 3];
+        for (c, value) in rgb.iter_mut().enumerate() {
+            let over = f32::from(background[c]) * (1.0 - alpha);
+            *value = (f32::from(pixel[c]) * alpha + over) / 255.0;
+        }
+        let [r, g, b] = rgb;
+        let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        planes[0].push(16.0 + 219.0 * y);
+        planes[1].push(128.0 + 224.0 * (b - y) / 1.8556);
+        planes[2].push(128.0 + 224.0 * (r - y) / 1.5748);
+    }
+    planes
+}
+
+/// Average a plane over blocks of `xs` × `ys` pixels, and round it to bytes.
+fn downsample(plane: &[f32], width: usize, xs: usize, ys: usize) -> Vec<u8> {
+    let height = plane.len() / width.max(1);
+    let mut out = Vec::new();
+    for by in (0..height).step_by(ys) {
+        for bx in (0..width).step_by(xs) {
+            let (mut sum, mut count) = (0.0, 0.0);
+            for y in by..(by + ys).min(height) {
+                for x in bx..(bx + xs).min(width) {
+                    sum += plane[y * width + x];
+                    count += 1.0;
+                }
+            }
+            out.push((sum / count).round().clamp(0.0, 255.0) as u8);
+        }
+    }
+    out
+}
+
+impl Clip {
+    /// Export the frames as a YUV4MPEG2 video stream, with one frame for
+    /// each sample, played once.  Returns `None` if the frames aren't all
+    /// the same size.
+    pub fn save_y4m<W: Write>(
+        &self,
+        mut writer: W,
+        settings: Y4mSettings,
+    ) -> Option<()> {
+        let (width, height) = {
+            let (raster, _) = self.frames.first()?;
+            (raster.width(), raster.height())
+        };
+        let (chroma, xs, ys) = match settings.subsampling {
+            Subsampling::C444 => ("444", 1, 1),
+            Subsampling::C422 => ("422", 2, 1),
+            Subsampling::C420 => ("420jpeg", 2, 2),
+        };
+        // Frame rate as a fraction, to a thousandth of a frame.
+        let num = (self.fps * 1000.0).round() as u32;
+        let divisor = gcd(num, 1000).max(1);
+        writeln!(
+            writer,
+            "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C{} XCOLORRANGE=LIMITED",
+            width,
+            height,
+            num / divisor,
+            1000 / divisor,
+            chroma,
+        )
+        .ok()?;
+        for raster in self.samples() {
+            if (raster.width(), raster.height()) != (width, height) {
+                return None;
+            }
+            let [y, cb, cr] = planes(raster, settings.background);
+            writer.write_all(b"FRAME\n").ok()?;
+            let width = width as usize;
+            writer.write_all(&downsample(&y, width, 1, 1)).ok()?;
+            writer.write_all(&downsample(&cb, width, xs, ys)).ok()?;
+            writer.write_all(&downsample(&cr, width, xs, ys)).ok()?;
+        }
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A clip of one 3 × 3 frame of a color, shown for `delay` ms.
+    fn clip(color: [u8; 4], delay: u32, fps: f32) -> Clip {
+        Clip {
+            frames: vec![(
+                Raster::with_u8_buffer(3, 3, color.repeat(9)),
+                delay,
+            )],
+            loops: 1,
+            fps,
+        }
+    }
+
+    /// Export a clip, and split it into its header and frames.
+    fn export(clip: &Clip, subsampling: Subsampling) -> (String, Vec<Vec<u8>>) {
+        let settings = Y4mSettings {
+            subsampling,
+            background: [0, 0, 255],
+        };
+        let mut y4m = Vec::new();
+        clip.save_y4m(&mut y4m, settings).unwrap();
+        let end = y4m.iter().position(|b| *b == b'\n').unwrap();
+        let header = String::from_utf8(y4m[..end].to_vec()).unwrap();
+        let mut frames: Vec<Vec<u8>> = Vec::new();
+        let mut rest = &y4m[end + 1..];
+        while !rest.is_empty() {
+            let next = rest
+                .windows(6)
+                .skip(1)
+                .position(|w| w == b"FRAME\n")
+                .map_or(rest.len(), |at| at + 1);
+            assert_eq!(&rest[..6], b"FRAME\n");
+            frames.push(rest[6..next].to_vec());
+            rest = &rest[next..];
+        }
+        (header, frames)
+    }
+
+    #[test]
+    fn chroma_sizes() {
+        let clip = clip([255, 255, 255, 255], 100, 10.0);
+        // Odd sizes round the chroma planes up.
+        for (subsampling, chroma, len) in &[
+            (Subsampling::C444, "C444", 9 + 9 + 9),
+            (Subsampling::C422, "C422", 9 + 6 + 6),
+            (Subsampling::C420, "C420jpeg", 9 + 4 + 4),
+        ] {
+            let (header, frames) = export(&clip, *subsampling);
+            assert_eq!(
+                header,
+                format!(
+                    "YUV4MPEG2 W3 H3 F10:1 Ip A1:1 {} XCOLORRANGE=LIMITED",
+                    chroma
+                )
+            );
+            assert_eq!(frames.len(), 1);
+            assert_eq!(frames[0].len(), *len);
+        }
+    }
+
+    #[test]
+    fn bt709_limited_range() {
+        let yuv = |color| {
+            let (_, frames) =
+                export(&clip(color, 100, 10.0), Subsampling::C444);
+            (frames[0][0], frames[0][9], frames[0][18])
+        };
+        assert_eq!(yuv([255, 255, 255, 255]), (235, 128, 128));
+        assert_eq!(yuv([0, 0, 0, 255]), (16, 128, 128));
+        assert_eq!(yuv([255, 0, 0, 255]), (63, 102, 240));
+        // Transparent pixels show the background.
+        assert_eq!(yuv([255, 0, 0, 0]), yuv([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn frame_rate_and_samples() {
+        // 250 ms at 29.97 frames a second is 8 samples.
+        let (header, frames) =
+            export(&clip([0, 0, 0, 255], 250, 29.97), Subsampling::C420);
+        assert!(header.contains(" F2997:100 "), "{}", header);
+        assert_eq!(frames.len(), 8);
+
+        let mut clip = clip([0, 0, 0, 255], 100, 10.0);
+        clip.frames.push((Raster::with_clear(2, 2), 100));
+        assert!(clip
+            .save_y4m(&mut Vec::new(), Y4mSettings::default())
+            .is_none());
+    }
+}